@@ -1,10 +1,12 @@
-use std::vec::Vec;
-use std::io::IoError;
+use std::io::{self, Write};
 
-#[deriving(Show, PartialOrd, PartialEq, Ord, Eq, Clone, Hash)]
+use crate::runtime;
+use crate::fingerprint;
+
+#[derive(Debug, PartialOrd, PartialEq, Ord, Eq, Clone, Hash)]
 pub enum Action {
     PushChar(char),
-    PushNumber(int),
+    PushNumber(i64),
     OutputChar,
     OutputNumber,
     InputChar,
@@ -14,254 +16,472 @@ pub enum Action {
     Subtract,
     Divide,
     Multiply,
+    Modulo,
+    Not,
+    Greater,
     Pop,
     Swap,
-    Random(uint, uint, uint, uint),
-    Jump(Vec<uint>),
-    If(uint, uint),
-    Compare(uint, uint, uint),
-    CallState(uint),
+    Random(Vec<usize>),
+    Jump(Vec<usize>),
+    If(usize, usize),
+    Compare(usize, usize, usize),
+    CallState(usize),
     Clear,
     End,
-    TablePut,
-    TableGet
+    Quit,
+    Execute,
+    FileInput(usize, usize, i64, i64, i64, i64, i64, i64),
+    FileOutput(usize, usize),
+    TablePut(i64, i64, i64, i64, i64, i64),
+    Store(i64, i64, i64, i64, i64, i64),
+    TableGet,
+    Iterate(Box<Action>),
+    Repeat(char),
+    Countdown(usize),
+    Split(usize),
+    Interpret(i64, i64, i64, i64, i64, i64),
+    Vector(Vec<((i64, i64, i64), usize)>, i64, i64, i64, i64, i64, i64),
+    BeginBlock(i64, i64, i64),
+    EndBlock(usize, usize),
+    Under(usize, usize),
+    SystemInfo(i64, i64, i64, i64, i64, i64),
+    LoadFingerprint(usize, usize),
+    UnloadFingerprint(usize, usize),
+    Semantic(char, usize, usize),
+    Call(&'static str)
 }
 
 impl Action {
-    pub fn write_to<W: Writer>(&self, writer: &mut W) -> Result<(), IoError> {
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         match self {
-            &Action::PushChar(c) => writer.write_line(format!("        self.stack.push('{}' as int);", c).as_slice()),
-            &Action::PushNumber(n) => writer.write_line(format!("        self.stack.push({});", n).as_slice()),
-            &Action::OutputChar => writer.write_line("        self.output_char();"),
-            &Action::OutputNumber => writer.write_line("        self.output_number();"),
-            &Action::InputChar => writer.write_line("        self.input_char();"),
-            &Action::Duplicate => writer.write_line("        self.duplicate();"),
-            &Action::Add => writer.write_line("        self.add();"),
-            &Action::Subtract => writer.write_line("        self.subtract();"),
-            &Action::Multiply => writer.write_line("        self.multiply();"),
-            &Action::Divide => writer.write_line("        self.divide();"),
-            &Action::Pop => writer.write_line("        self.stack.pop();"),
-            &Action::Swap => writer.write_line("        self.swap();"),
-
-            &Action::Random(u, d, l, r) => {
-                writer.write_line("        match random::<uint>() % 4 {")
-                .and_then(|_| writer.write_line(format!("            0 => self.state{}(),", u).as_slice()))
-                .and_then(|_| writer.write_line(format!("            1 => self.state{}(),", d).as_slice()))
-                .and_then(|_| writer.write_line(format!("            2 => self.state{}(),", l).as_slice()))
-                .and_then(|_| writer.write_line(format!("            3 => self.state{}(),", r).as_slice()))
-                .and_then(|_| writer.write_line("            _ => ()"))
-                .and_then(|_| writer.write_line("        }"))
+            Action::PushChar(c) => writeln!(writer, "        self.stack.push({:?} as i64);", c),
+            Action::PushNumber(n) => writeln!(writer, "        self.stack.push({});", n),
+            Action::OutputChar => writeln!(writer, "        self.output_char();"),
+            Action::OutputNumber => writeln!(writer, "        self.output_number();"),
+            Action::InputChar => writeln!(writer, "        self.input_char();"),
+            Action::InputNumber => writeln!(writer, "        self.input_number();"),
+            Action::Duplicate => writeln!(writer, "        self.duplicate();"),
+            Action::Add => writeln!(writer, "        self.add();"),
+            Action::Subtract => writeln!(writer, "        self.subtract();"),
+            Action::Multiply => writeln!(writer, "        self.multiply();"),
+            Action::Divide => writeln!(writer, "        self.divide();"),
+            Action::Modulo => writeln!(writer, "        self.modulo();"),
+            Action::Not => writeln!(writer, "        self.not();"),
+            Action::Greater => writeln!(writer, "        self.greater();"),
+            Action::Pop => writeln!(writer, "        self.stack.pop();"),
+            Action::Swap => writeln!(writer, "        self.swap();"),
+
+            Action::Random(v) => {
+                writeln!(writer, "        match self.rng.next() % {} {{", v.len())?;
+                for (i, s) in v.iter().enumerate() {
+                    writeln!(writer, "            {} => Some({}),", i, s)?;
+                }
+                writeln!(writer, "            _ => unreachable!()")?;
+                writeln!(writer, "        }}")
             }
 
-            &Action::Jump(ref v) => {
-                writer.write_line("        match self.stack.pop() {")
-                .and_then(|_| writer.write_line(format!("            Some(n) => match modulus(n, {}) {{", v.len()).as_slice()))
-                .and_then(|_| range(0, v.len()).fold(Ok(()), |a, i| a.and_then(|_| writer.write_line(format!("                {} => self.state{}(),", i, v[i]).as_slice()))))
-                .and_then(|_| writer.write_line("                _ => ()"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line(format!("            None => self.state{}()", v[0]).as_slice()))
-                .and_then(|_| writer.write_line("        }"))
+            Action::Jump(v) => {
+                writeln!(writer, "        match self.stack.pop() {{")?;
+                writeln!(writer, "            Some(n) => match n.rem_euclid({}) {{", v.len())?;
+                for (i, s) in v.iter().enumerate() {
+                    writeln!(writer, "                {} => Some({}),", i, s)?;
+                }
+                writeln!(writer, "                _ => unreachable!()")?;
+                writeln!(writer, "            }},")?;
+                writeln!(writer, "            None => Some({})", v[0])?;
+                writeln!(writer, "        }}")
             },
 
-            &Action::If(t, f) => {
-                writer.write_line("        match self.stack.pop() {")
-                .and_then(|_| writer.write_line(format!("            Some(0) | None => self.state{}(),", f).as_slice()))
-                .and_then(|_| writer.write_line(format!("            Some(_) => self.state{}(),", t).as_slice()))
-                .and_then(|_| writer.write_line("        }"))
+            Action::If(t, f) => {
+                writeln!(writer, "        match self.stack.pop() {{")?;
+                writeln!(writer, "            Some(0) | None => Some({}),", f)?;
+                writeln!(writer, "            Some(_) => Some({}),", t)?;
+                writeln!(writer, "        }}")
             },
 
-            &Action::Compare(s, l, r) => {
-                writer.write_line("        match (self.stack.pop(), self.stack.pop()) {")
-                .and_then(|_| writer.write_line(format!("            (Some(a), Some(b)) if a < b => self.state{}(),", l).as_slice()))
-                .and_then(|_| writer.write_line(format!("            (Some(a), Some(b)) if a > b => self.state{}(),", r).as_slice()))
-                .and_then(|_| writer.write_line(format!("            (Some(_), Some(_)) | (Some(0), None) | (None, Some(0)) | (None, None) => self.state{}(),", s).as_slice()))
-                .and_then(|_| writer.write_line(format!("            (None, _) => self.state{}(),", l).as_slice()))
-                .and_then(|_| writer.write_line(format!("            (_, None) => self.state{}()", r).as_slice()))
-                .and_then(|_| writer.write_line("        }"))
+            Action::Compare(s, l, r) => {
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(a), Some(b)) if a < b => Some({}),", l)?;
+                writeln!(writer, "            (Some(a), Some(b)) if a > b => Some({}),", r)?;
+                writeln!(writer, "            (Some(_), Some(_)) | (Some(0), None) | (None, Some(0)) | (None, None) => Some({}),", s)?;
+                writeln!(writer, "            (None, _) => Some({}),", l)?;
+                writeln!(writer, "            (_, None) => Some({})", r)?;
+                writeln!(writer, "        }}")
             },
 
-            &Action::CallState(s) => writer.write_line(format!("        self.state{}()", s).as_slice()),
+            Action::CallState(s) => writeln!(writer, "        Some({})", s),
 
-            &Action::Clear => writer.write_line("        self.stack.clear();"),
+            Action::Clear => writeln!(writer, "        self.stack.clear();"),
 
-            &Action::End => writer.write_line("        ()"),
+            Action::End => writeln!(writer, "        None"),
+            Action::Quit => writeln!(writer, "        self.quit()"),
+            Action::Execute => writeln!(writer, "        self.execute();"),
 
-            &Action::TableGet => writer.write_line("        self.table_get();"),
-            &Action::TablePut => writer.write_line("        self.table_put();"),
+            // The embedded interpreter only takes over once the file has overwritten a compiled cell
+            Action::FileInput(t, f, x, y, z, dx, dy, dz) => {
+                writeln!(writer, "        match self.file_input() {{")?;
+                writeln!(writer, "            Some(false) => Some({}),", t)?;
+                writeln!(writer, "            None => Some({}),", f)?;
+                writeln!(writer, "            Some(true) => {{")?;
+                writeln!(writer, "                self.interpret({}, {}, {}, {}, {}, {}, true);", x, y, z, dx, dy, dz)?;
+                writeln!(writer, "                None")?;
+                writeln!(writer, "            }}")?;
+                writeln!(writer, "        }}")
+            },
 
-            _ => Ok(())
+            Action::FileOutput(t, f) => {
+                writeln!(writer, "        if self.file_output() {{")?;
+                writeln!(writer, "            Some({})", t)?;
+                writeln!(writer, "        }} else {{")?;
+                writeln!(writer, "            Some({})", f)?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::TableGet => writeln!(writer, "        self.table_get();"),
+            Action::TablePut(x, y, z, dx, dy, dz) => {
+                writeln!(writer, "        if self.table_put() {{")?;
+                writeln!(writer, "            self.interpret({}, {}, {}, {}, {}, {}, true);", x, y, z, dx, dy, dz)?;
+                writeln!(writer, "            return None;")?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::Store(x, y, z, dx, dy, dz) => {
+                writeln!(writer, "        if self.store({}, {}, {}) {{", x, y, z)?;
+                writeln!(writer, "            let (x, y, z) = self.step({}, {}, {}, {}, {}, {});", x, y, z, dx, dy, dz)?;
+                writeln!(writer, "            self.interpret(x, y, z, {}, {}, {}, true);", dx, dy, dz)?;
+                writeln!(writer, "            return None;")?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::Iterate(action) => {
+                let mut body = Vec::new();
+                action.write_to(&mut body)?;
+
+                writeln!(writer, "        for _ in 0..self.stack.pop().unwrap_or(0) {{")?;
+                for line in String::from_utf8_lossy(&body).lines() {
+                    writeln!(writer, "    {}", line)?;
+                }
+                writeln!(writer, "        }}")
+            },
+
+            // The count is kept the way the embedded interpreter keeps it, so it can pick up a repetition where it stands
+            Action::Repeat(c) => writeln!(writer, "        self.iterate = Some(({:?} as i64, self.stack.pop().unwrap_or(0)));", c),
+
+            Action::Countdown(done) => {
+                writeln!(writer, "        match self.iterate {{")?;
+                writeln!(writer, "            Some((c, n)) if n > 0 => self.iterate = Some((c, n - 1)),")?;
+                writeln!(writer, "            _ => {{")?;
+                writeln!(writer, "                self.iterate = None;")?;
+                writeln!(writer, "                return self.state{}();", done)?;
+                writeln!(writer, "            }}")?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::Split(state) => writeln!(writer, "        self.split({});", state),
+
+            Action::Interpret(x, y, z, dx, dy, dz) => {
+                writeln!(writer, "        self.interpret({}, {}, {}, {}, {}, {}, false);", x, y, z, dx, dy, dz)?;
+                writeln!(writer, "        None")
+            },
+
+            Action::BeginBlock(x, y, z) => writeln!(writer, "        self.begin_block(({}, {}, {}));", x, y, z),
+
+            Action::EndBlock(t, f) => {
+                writeln!(writer, "        if self.end_block() {{")?;
+                writeln!(writer, "            Some({})", t)?;
+                writeln!(writer, "        }} else {{")?;
+                writeln!(writer, "            Some({})", f)?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::Under(t, f) => {
+                writeln!(writer, "        if self.under() {{")?;
+                writeln!(writer, "            Some({})", t)?;
+                writeln!(writer, "        }} else {{")?;
+                writeln!(writer, "            Some({})", f)?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::LoadFingerprint(t, f) => {
+                writeln!(writer, "        if self.load_fingerprint() {{")?;
+                writeln!(writer, "            Some({})", t)?;
+                writeln!(writer, "        }} else {{")?;
+                writeln!(writer, "            Some({})", f)?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::UnloadFingerprint(t, f) => {
+                writeln!(writer, "        if self.unload_fingerprint() {{")?;
+                writeln!(writer, "            Some({})", t)?;
+                writeln!(writer, "        }} else {{")?;
+                writeln!(writer, "            Some({})", f)?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::Semantic(c, t, f) => {
+                writeln!(writer, "        if self.semantic({:?}) {{", c)?;
+                writeln!(writer, "            Some({})", t)?;
+                writeln!(writer, "        }} else {{")?;
+                writeln!(writer, "            Some({})", f)?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::Call(name) => {
+                writeln!(writer, "        if !self.{}() {{", name)?;
+                writeln!(writer, "            return false;")?;
+                writeln!(writer, "        }}")
+            },
+
+            Action::SystemInfo(x, y, z, dx, dy, dz) => writeln!(writer, "        self.system_info(({}, {}, {}), ({}, {}, {}));", x, y, z, dx, dy, dz),
+
+            Action::Vector(targets, x, y, z, dx, dy, dz) => {
+                writeln!(writer, "        match self.pop_vector() {{")?;
+                for ((tdx, tdy, tdz), s) in targets.iter() {
+                    writeln!(writer, "            ({}, {}, {}) => Some({}),", tdx, tdy, tdz, s)?;
+                }
+                writeln!(writer, "            delta => {{")?;
+                writeln!(writer, "                self.push_vector(delta);")?;
+                writeln!(writer, "                self.interpret({}, {}, {}, {}, {}, {}, false);", x, y, z, dx, dy, dz)?;
+                writeln!(writer, "                None")?;
+                writeln!(writer, "            }}")?;
+                writeln!(writer, "        }}")
+            }
         }
     }
 
-    pub fn write_impl_to<W: Writer>(&self, writer: &mut W) -> Result<(), IoError> {
+    pub fn write_impl_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         match self {
-            &Action::Duplicate => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn duplicate(&mut self) {"))
-                .and_then(|_| writer.write_line("        match self.stack.pop() {"))
-                .and_then(|_| writer.write_line("            Some(c) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(c);"))
-                .and_then(|_| writer.write_line("                self.stack.push(c);"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line("            None => ()"))
-                .and_then(|_| writer.write_line("        }"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::Add => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn add(&mut self) {"))
-                .and_then(|_| writer.write_line("        match (self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(a), Some(b)) => self.stack.push(a + b),"))
-                .and_then(|_| writer.write_line("            (Some(a), None) | (None, Some(a)) => self.stack.push(a),"))
-                .and_then(|_| writer.write_line("            (None, None) => self.stack.push(0)"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-
-            &Action::Subtract => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn subtract(&mut self) {"))
-                .and_then(|_| writer.write_line("        match (self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(a), Some(b)) => self.stack.push(b - a),"))
-                .and_then(|_| writer.write_line("            (Some(a), None) => self.stack.push(-a),"))
-                .and_then(|_| writer.write_line("            (None, Some(a)) => self.stack.push(a),"))
-                .and_then(|_| writer.write_line("            (None, None) => self.stack.push(0)"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::Multiply => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn multiply(&mut self) {"))
-                .and_then(|_| writer.write_line("        match (self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(a), Some(b)) => self.stack.push(a * b),"))
-                .and_then(|_| writer.write_line("            (_, None) | (None, _) => self.stack.push(0)"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::Divide => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn divide(&mut self) {"))
-                .and_then(|_| writer.write_line("        match (self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(a), Some(b)) => self.stack.push(b / a),"))
-                .and_then(|_| writer.write_line("            (Some(a), None) => self.stack.push(0 / a),"))
-                .and_then(|_| writer.write_line("            (None, Some(a)) => self.stack.push(a / 0),"))
-                .and_then(|_| writer.write_line("            (None, None) => self.stack.push(0 / 0)"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::Swap => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn swap(&mut self) {"))
-                .and_then(|_| writer.write_line("        match (self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(a), Some(b)) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(a);"))
-                .and_then(|_| writer.write_line("                self.stack.push(b);"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line(""))
-                .and_then(|_| writer.write_line("            (Some(a), None) => self.stack.push(a),"))
-                .and_then(|_| writer.write_line(""))
-                .and_then(|_| writer.write_line("            (None, Some(a)) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(0);"))
-                .and_then(|_| writer.write_line("                self.stack.push(a);"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line(""))
-                .and_then(|_| writer.write_line("            _ => self.stack.push(0)"))
-                .and_then(|_| writer.write_line("        }"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::OutputChar => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn output_char(&mut self) {"))
-                .and_then(|_| writer.write_line("        match self.stack.pop() {"))
-                .and_then(|_| writer.write_line("            Some(c) if char::from_u32(c as u32).is_some() => self.output.write_char(char::from_u32(c as u32).unwrap()),"))
-                .and_then(|_| writer.write_line("            _ => self.output.write_char(0 as char)"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line(""))
-                .and_then(|_| writer.write_line("        self.output.flush();"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::OutputNumber => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn output_number(&mut self) {"))
-                .and_then(|_| writer.write_line("        match self.stack.pop() {"))
-                .and_then(|_| writer.write_line("            Some(n) => self.output.write_int(n),"))
-                .and_then(|_| writer.write_line("            None => self.output.write_int(0)"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-    
-            &Action::InputChar => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn input_char(&mut self) {"))
-                .and_then(|_| writer.write_line("        self.stack.push(self.input.read_char().unwrap() as int);"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::TableGet => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn table_get(&mut self) {"))
-                .and_then(|_| writer.write_line("        match(self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(y), Some(x)) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(match self.table.entry((x, y)) {"))
-                .and_then(|_| writer.write_line("                    Entry::Vacant(entry) => {"))
-                .and_then(|_| writer.write_line("                        entry.set(' ' as int);"))
-                .and_then(|_| writer.write_line("                        ' ' as int"))
-                .and_then(|_| writer.write_line("                    },"))
-                .and_then(|_| writer.write_line("                    Entry::Occupied(entry) => entry.get().clone()"))
-                .and_then(|_| writer.write_line("                });"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line("            (None, Some(x)) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(match self.table.entry((x, 0)) {"))
-                .and_then(|_| writer.write_line("                    Entry::Vacant(entry) => {"))
-                .and_then(|_| writer.write_line("                        entry.set(' ' as int);"))
-                .and_then(|_| writer.write_line("                        ' ' as int"))
-                .and_then(|_| writer.write_line("                    },"))
-                .and_then(|_| writer.write_line("                    Entry::Occupied(entry) => entry.get().clone()"))
-                .and_then(|_| writer.write_line("                });"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line("            (Some(y), None) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(match self.table.entry((0, y)) {"))
-                .and_then(|_| writer.write_line("                    Entry::Vacant(entry) => {"))
-                .and_then(|_| writer.write_line("                        entry.set(' ' as int);"))
-                .and_then(|_| writer.write_line("                        ' ' as int"))
-                .and_then(|_| writer.write_line("                    },"))
-                .and_then(|_| writer.write_line("                    Entry::Occupied(entry) => entry.get().clone()"))
-                .and_then(|_| writer.write_line("                });"))
-                .and_then(|_| writer.write_line("            },"))
-                .and_then(|_| writer.write_line("            (None, None) => {"))
-                .and_then(|_| writer.write_line("                self.stack.push(match self.table.entry((0, 0)) {"))
-                .and_then(|_| writer.write_line("                    Entry::Vacant(entry) => {"))
-                .and_then(|_| writer.write_line("                        entry.set(' ' as int);"))
-                .and_then(|_| writer.write_line("                        ' ' as int"))
-                .and_then(|_| writer.write_line("                    },"))
-                .and_then(|_| writer.write_line("                    Entry::Occupied(entry) => entry.get().clone()"))
-                .and_then(|_| writer.write_line("                });"))
-                .and_then(|_| writer.write_line("            }"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
-            },
-
-            &Action::TablePut => {
-                writer.write_line("")
-                .and_then(|_| writer.write_line("    fn table_put(&mut self) {"))
-                .and_then(|_| writer.write_line("        match(self.stack.pop(), self.stack.pop()) {"))
-                .and_then(|_| writer.write_line("            (Some(y), Some(x)) => self.table.insert((x, y), self.stack.pop().unwrap_or(0)),"))
-                .and_then(|_| writer.write_line("            (None, Some(x)) => self.table.insert((x, 0), self.stack.pop().unwrap_or(0)),"))
-                .and_then(|_| writer.write_line("            (Some(y), None) => self.table.insert((0, y), self.stack.pop().unwrap_or(0)),"))
-                .and_then(|_| writer.write_line("            (None, None) => self.table.insert((0, 0), self.stack.pop().unwrap_or(0))"))
-                .and_then(|_| writer.write_line("        };"))
-                .and_then(|_| writer.write_line("    }"))
+            Action::Duplicate => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn duplicate(&mut self) {{")?;
+                writeln!(writer, "        if let Some(c) = self.stack.pop() {{")?;
+                writeln!(writer, "            self.stack.push(c);")?;
+                writeln!(writer, "            self.stack.push(c);")?;
+                writeln!(writer, "        }}")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Add => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn add(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(a), Some(b)) => self.stack.push(a.wrapping_add(b)),")?;
+                writeln!(writer, "            (Some(a), None) | (None, Some(a)) => self.stack.push(a),")?;
+                writeln!(writer, "            (None, None) => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
             },
 
+            Action::Subtract => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn subtract(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(a), Some(b)) => self.stack.push(b.wrapping_sub(a)),")?;
+                writeln!(writer, "            (Some(a), None) => self.stack.push(a.wrapping_neg()),")?;
+                writeln!(writer, "            (None, Some(a)) => self.stack.push(a),")?;
+                writeln!(writer, "            (None, None) => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Multiply => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn multiply(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(a), Some(b)) => self.stack.push(a.wrapping_mul(b)),")?;
+                writeln!(writer, "            (_, None) | (None, _) => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Divide => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn divide(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(0), _) | (None, _) => self.stack.push(0),")?;
+                writeln!(writer, "            (Some(a), Some(b)) => self.stack.push(b.wrapping_div(a)),")?;
+                writeln!(writer, "            (Some(_), None) => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Modulo => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn modulo(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(0), _) | (None, _) => self.stack.push(0),")?;
+                writeln!(writer, "            (Some(a), Some(b)) => self.stack.push(b.wrapping_rem(a)),")?;
+                writeln!(writer, "            (Some(_), None) => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Not => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn not(&mut self) {{")?;
+                writeln!(writer, "        match self.stack.pop() {{")?;
+                writeln!(writer, "            Some(0) | None => self.stack.push(1),")?;
+                writeln!(writer, "            Some(_) => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Greater => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn greater(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop().unwrap_or(0), self.stack.pop().unwrap_or(0)) {{")?;
+                writeln!(writer, "            (a, b) if b > a => self.stack.push(1),")?;
+                writeln!(writer, "            _ => self.stack.push(0)")?;
+                writeln!(writer, "        }};")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Swap => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn swap(&mut self) {{")?;
+                writeln!(writer, "        match (self.stack.pop(), self.stack.pop()) {{")?;
+                writeln!(writer, "            (Some(a), Some(b)) => {{")?;
+                writeln!(writer, "                self.stack.push(a);")?;
+                writeln!(writer, "                self.stack.push(b);")?;
+                writeln!(writer, "            }},")?;
+                writeln!(writer)?;
+                writeln!(writer, "            (Some(a), None) => self.stack.push(a),")?;
+                writeln!(writer)?;
+                writeln!(writer, "            (None, Some(a)) => {{")?;
+                writeln!(writer, "                self.stack.push(0);")?;
+                writeln!(writer, "                self.stack.push(a);")?;
+                writeln!(writer, "            }},")?;
+                writeln!(writer)?;
+                writeln!(writer, "            _ => self.stack.push(0)")?;
+                writeln!(writer, "        }}")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::OutputChar => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn output_char(&mut self) {{")?;
+                writeln!(writer, "        let c = self.stack.pop().and_then(|c| u32::try_from(c).ok()).and_then(char::from_u32);")?;
+                writeln!(writer, "        let _ = write!(self.output, \"{{}}\", c.unwrap_or('\\0'));")?;
+                writeln!(writer)?;
+                writeln!(writer, "        let _ = self.output.flush();")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::OutputNumber => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn output_number(&mut self) {{")?;
+                writeln!(writer, "        let n = self.stack.pop().unwrap_or(0);")?;
+                writeln!(writer, "        let _ = write!(self.output, \"{{}}\", n);")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::InputChar => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn input_char(&mut self) {{")?;
+                writeln!(writer, "        let c = self.read_char().map_or(-1, |c| c as i64);")?;
+                writeln!(writer, "        self.stack.push(c);")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::InputNumber => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn input_number(&mut self) {{")?;
+                writeln!(writer, "        let mut negative = false;")?;
+                writeln!(writer, "        let mut number: Option<i64> = None;")?;
+                writeln!(writer)?;
+                writeln!(writer, "        loop {{")?;
+                writeln!(writer, "            match self.read_char() {{")?;
+                writeln!(writer, "                Some(c) if c.is_ascii_digit() => number = Some(number.unwrap_or(0).wrapping_mul(10).wrapping_add(c.to_digit(10).unwrap() as i64)),")?;
+                writeln!(writer, "                Some('-') if number.is_none() => negative = true,")?;
+                writeln!(writer, "                Some(_) if number.is_none() => negative = false,")?;
+                writeln!(writer, "                _ => break")?;
+                writeln!(writer, "            }}")?;
+                writeln!(writer, "        }}")?;
+                writeln!(writer)?;
+                writeln!(writer, "        self.stack.push(match number {{")?;
+                writeln!(writer, "            Some(n) if negative => -n,")?;
+                writeln!(writer, "            Some(n) => n,")?;
+                writeln!(writer, "            None => -1")?;
+                writeln!(writer, "        }});")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::TableGet => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn table_get(&mut self) {{")?;
+                writeln!(writer, "        let (x, y, z) = self.pop_vector();")?;
+                writeln!(writer, "        let (x, y, z) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));")?;
+                writeln!(writer)?;
+                writeln!(writer, "        // Befunge-93 has nothing outside its grid, and reads 0 from there")?;
+                writeln!(writer, "        let outside = x < 0 || y < 0 || z < 0 || x >= WIDTH || y >= HEIGHT || z >= DEPTH;")?;
+                writeln!(writer, "        let value = if BEFUNGE93 && outside {{ 0 }} else {{ self.cell(x, y, z) }};")?;
+                writeln!(writer, "        self.stack.push(value);")?;
+                writeln!(writer, "    }}")?;
+                writeln!(writer)?;
+                writeln!(writer, "    fn cell(&self, x: i64, y: i64, z: i64) -> i64 {{")?;
+                writeln!(writer, "        match self.table.get(&(x, y, z)) {{")?;
+                writeln!(writer, "            Some(value) => *value,")?;
+                writeln!(writer, "            None if x >= 0 && y >= 0 && z >= 0 && x < WIDTH && y < HEIGHT && z < DEPTH => CODE[z as usize][y as usize][x as usize],")?;
+                writeln!(writer, "            None => ' ' as i64")?;
+                writeln!(writer, "        }}")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::TablePut(..) => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn table_put(&mut self) -> bool {{")?;
+                writeln!(writer, "        let (x, y, z) = self.pop_vector();")?;
+                writeln!(writer, "        let (x, y, z) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));")?;
+                writeln!(writer, "        let value = self.stack.pop().unwrap_or(0);")?;
+                writeln!(writer, "        let inside = x >= 0 && y >= 0 && z >= 0 && x < WIDTH && y < HEIGHT && z < DEPTH;")?;
+                writeln!(writer)?;
+                writeln!(writer, "        // Befunge-93 ignores writes outside its grid")?;
+                writeln!(writer, "        if BEFUNGE93 && !inside {{")?;
+                writeln!(writer, "            return false;")?;
+                writeln!(writer, "        }}")?;
+                writeln!(writer)?;
+                writeln!(writer, "        self.table.insert((x, y, z), value);")?;
+                writeln!(writer)?;
+                writeln!(writer, "        // Writing over a compiled cell invalidates the compiled code from here on")?;
+                writeln!(writer, "        inside")?;
+                writeln!(writer, "            && COMPILED[z as usize][y as usize][x as usize]")?;
+                writeln!(writer, "            && CODE[z as usize][y as usize][x as usize] != value")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Store(..) => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn store(&mut self, x: i64, y: i64, z: i64) -> bool {{")?;
+                writeln!(writer, "        let value = self.stack.pop().unwrap_or(0);")?;
+                writeln!(writer, "        self.table.insert((x, y, z), value);")?;
+                writeln!(writer)?;
+                writeln!(writer, "        COMPILED[z as usize][y as usize][x as usize] && CODE[z as usize][y as usize][x as usize] != value")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Quit => {
+                writeln!(writer)?;
+                writeln!(writer, "    fn quit(&mut self) -> ! {{")?;
+                writeln!(writer, "        let code = self.stack.pop().unwrap_or(0);")?;
+                writeln!(writer, "        let _ = std::io::Write::flush(&mut std::io::stdout());")?;
+                writeln!(writer, "        std::process::exit(code as i32)")?;
+                writeln!(writer, "    }}")
+            },
+
+            Action::Execute => runtime::write_execute(writer),
+            Action::FileInput(..) => runtime::write_file_input(writer),
+            Action::FileOutput(..) => runtime::write_file_output(writer),
+
+            Action::Interpret(..) => runtime::write_interpreter(writer),
+
+            Action::BeginBlock(..) => runtime::write_begin_block(writer),
+            Action::EndBlock(..) => runtime::write_end_block(writer),
+            Action::Under(..) => runtime::write_under(writer),
+            Action::SystemInfo(..) => runtime::write_system_info(writer),
+
+            Action::LoadFingerprint(..) => fingerprint::write_runtime(writer),
+
             _ => Ok(())
         }
     }
 }
-
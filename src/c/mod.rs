@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::action::Action;
+
+pub fn supports(action: &Action) -> bool {
+    match action {
+        Action::TablePut(..) | Action::Store(..) | Action::TableGet | Action::Interpret(..) | Action::Split(_) | Action::Vector(..)
+        | Action::BeginBlock(..) | Action::EndBlock(..) | Action::Under(..) | Action::SystemInfo(..) | Action::Execute
+        | Action::FileInput(..) | Action::FileOutput(..)
+        | Action::LoadFingerprint(..) | Action::UnloadFingerprint(..) | Action::Semantic(..) | Action::Call(_) => false,
+        Action::Iterate(action) => supports(action),
+        _ => true
+    }
+}
+
+pub fn write_first<W: Write>(writer: &mut W, used_actions: &HashSet<Action>) -> io::Result<()> {
+    writeln!(writer, "#include <stdio.h>")?;
+    writeln!(writer, "#include <stdlib.h>")?;
+
+    if used_actions.contains(&Action::Random(Vec::new())) {
+        writeln!(writer, "#include <time.h>")?;
+    }
+
+    writeln!(writer, "
+#define STACK_SIZE 1048576
+
+static long long stack[STACK_SIZE];
+static size_t sp = 0;
+
+static void push(long long v) {{
+    if (sp >= STACK_SIZE) {{
+        abort();
+    }}
+
+    stack[sp++] = v;
+}}
+
+static long long pop(void) {{
+    return sp == 0 ? 0 : stack[--sp];
+}}")?;
+
+    if used_actions.contains(&Action::Repeat(' ')) {
+        writeln!(writer, "\nstatic long long repeat = 0;")?;
+    }
+
+    for action in used_actions.iter() {
+        write_impl_to(writer, action)?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "int main(void) {{")?;
+
+    if used_actions.contains(&Action::Random(Vec::new())) {
+        writeln!(writer, "    srand((unsigned int) time(NULL));")?;
+    }
+
+    Ok(())
+}
+
+pub fn write_state<W: Write>(writer: &mut W, state: usize, actions: &[Action]) -> io::Result<()> {
+    writeln!(writer, "\nstate{}:", state)?;
+
+    for action in actions.iter() {
+        write_to(writer, action)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_end<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "}}")
+}
+
+fn write_to<W: Write>(writer: &mut W, action: &Action) -> io::Result<()> {
+    match action {
+        Action::PushChar(c) => writeln!(writer, "    push({});", *c as i64),
+        Action::PushNumber(n) => writeln!(writer, "    push({}LL);", n),
+        Action::OutputChar => writeln!(writer, "    output_char();"),
+        Action::OutputNumber => writeln!(writer, "    output_number();"),
+        Action::InputChar => writeln!(writer, "    input_char();"),
+        Action::InputNumber => writeln!(writer, "    input_number();"),
+        Action::Duplicate => writeln!(writer, "    duplicate();"),
+        Action::Add => writeln!(writer, "    add();"),
+        Action::Subtract => writeln!(writer, "    subtract();"),
+        Action::Multiply => writeln!(writer, "    multiply();"),
+        Action::Divide => writeln!(writer, "    divide();"),
+        Action::Modulo => writeln!(writer, "    modulo();"),
+        Action::Not => writeln!(writer, "    not();"),
+        Action::Greater => writeln!(writer, "    greater();"),
+        Action::Pop => writeln!(writer, "    pop();"),
+        Action::Swap => writeln!(writer, "    swap();"),
+        Action::Clear => writeln!(writer, "    sp = 0;"),
+
+        Action::Random(v) => {
+            writeln!(writer, "    switch (rand() % {}) {{", v.len())?;
+
+            for (i, target) in v.iter().enumerate().skip(1) {
+                writeln!(writer, "        case {}: goto state{};", i, target)?;
+            }
+
+            writeln!(writer, "        default: goto state{};", v[0])?;
+            writeln!(writer, "    }}")
+        },
+
+        Action::Jump(v) => {
+            writeln!(writer, "    switch (modulus(pop(), {})) {{", v.len())?;
+
+            for (i, target) in v.iter().enumerate().skip(1) {
+                writeln!(writer, "        case {}: goto state{};", i, target)?;
+            }
+
+            writeln!(writer, "        default: goto state{};", v[0])?;
+            writeln!(writer, "    }}")
+        },
+
+        Action::If(t, f) => {
+            writeln!(writer, "    if (pop()) {{")?;
+            writeln!(writer, "        goto state{};", t)?;
+            writeln!(writer, "    }} else {{")?;
+            writeln!(writer, "        goto state{};", f)?;
+            writeln!(writer, "    }}")
+        },
+
+        Action::Compare(s, l, r) => {
+            writeln!(writer, "    {{")?;
+            writeln!(writer, "        long long a = pop();")?;
+            writeln!(writer, "        long long b = pop();")?;
+            writeln!(writer)?;
+            writeln!(writer, "        if (a < b) {{")?;
+            writeln!(writer, "            goto state{};", l)?;
+            writeln!(writer, "        }} else if (a > b) {{")?;
+            writeln!(writer, "            goto state{};", r)?;
+            writeln!(writer, "        }} else {{")?;
+            writeln!(writer, "            goto state{};", s)?;
+            writeln!(writer, "        }}")?;
+            writeln!(writer, "    }}")
+        },
+
+        Action::Iterate(action) => {
+            writeln!(writer, "    for (long long n = pop(); n > 0; n--) {{")?;
+            write!(writer, "    ")?;
+            write_to(writer, action)?;
+            writeln!(writer, "    }}")
+        },
+
+        Action::Repeat(_) => writeln!(writer, "    repeat = pop();"),
+
+        Action::Countdown(done) => {
+            writeln!(writer, "    if (repeat <= 0) {{")?;
+            writeln!(writer, "        goto state{};", done)?;
+            writeln!(writer, "    }}")?;
+            writeln!(writer)?;
+            writeln!(writer, "    repeat--;")
+        },
+
+        Action::CallState(s) => writeln!(writer, "    goto state{};", s),
+
+        Action::End => writeln!(writer, "    return 0;"),
+        Action::Quit => writeln!(writer, "    return (int) pop();"),
+
+        _ => Ok(())
+    }
+}
+
+fn write_impl_to<W: Write>(writer: &mut W, action: &Action) -> io::Result<()> {
+    match action {
+        Action::Duplicate => writeln!(writer, "
+static void duplicate(void) {{
+    long long v = pop();
+    push(v);
+    push(v);
+}}"),
+
+        Action::Add => write_binary(writer, "add", "b + a"),
+        Action::Subtract => write_binary(writer, "subtract", "b - a"),
+        Action::Multiply => write_binary(writer, "multiply", "b * a"),
+        Action::Divide => write_binary(writer, "divide", "a == 0 ? 0 : b / a"),
+        Action::Modulo => write_binary(writer, "modulo", "a == 0 ? 0 : b % a"),
+        Action::Greater => write_binary(writer, "greater", "b > a"),
+
+        Action::Not => writeln!(writer, "
+static void not(void) {{
+    push(pop() == 0);
+}}"),
+
+        Action::Swap => writeln!(writer, "
+static void swap(void) {{
+    long long a = pop();
+    long long b = pop();
+    push(a);
+    push(b);
+}}"),
+
+        Action::OutputChar => writeln!(writer, "
+static void output_char(void) {{
+    putchar((int) pop());
+    fflush(stdout);
+}}"),
+
+        Action::OutputNumber => writeln!(writer, "
+static void output_number(void) {{
+    printf(\"%lld\", pop());
+}}"),
+
+        Action::InputChar => writeln!(writer, "
+static void input_char(void) {{
+    push(getchar());
+}}"),
+
+        Action::InputNumber => writeln!(writer, "
+static void input_number(void) {{
+    int negative = 0;
+    int c;
+    long long n;
+
+    while ((c = getchar()) != EOF && (c < '0' || c > '9')) {{
+        negative = c == '-';
+    }}
+
+    if (c == EOF) {{
+        push(-1);
+        return;
+    }}
+
+    n = c - '0';
+    while ((c = getchar()) >= '0' && c <= '9') {{
+        n = n * 10 + (c - '0');
+    }}
+
+    push(negative ? -n : n);
+}}"),
+
+        Action::Jump(_) => writeln!(writer, "
+static long long modulus(long long a, long long b) {{
+    long long m = a % b;
+    return m < 0 ? m + b : m;
+}}"),
+
+        _ => Ok(())
+    }
+}
+
+fn write_binary<W: Write>(writer: &mut W, name: &str, expr: &str) -> io::Result<()> {
+    writeln!(writer, "
+static void {}(void) {{
+    long long a = pop();
+    long long b = pop();
+    push({});
+}}", name, expr)
+}
@@ -1,37 +1,51 @@
-use std::fmt::{Show, Formatter, Result};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 
-#[deriving(Clone)]
+use crate::action::Action;
+
+#[derive(Debug, Clone)]
 pub enum ParserError {
     CmdError,
     FileReadError(String),
     FileEmptyError(String),
-    UnexpectedChar(int, int, char),
+    EmptySource,
+    SourceTooLarge(usize, usize),
+    UnexpectedChar(i64, i64, char),
+    UnsupportedInstruction(char, String),
     VarsDisabled,
     OutputError,
-    OutputFileError(String)
+    OutputFileError(String),
+    UnsupportedAction(String, Action)
 }
 
-impl Show for ParserError {
-    fn fmt(&self, f: &mut Formatter) -> Result {
+impl Display for ParserError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            &ParserError::CmdError => write!(f, "Error parsing command line args"),
-            &ParserError::FileReadError(ref file) => write!(f, "Unable to read file: {}", file),
-            &ParserError::FileEmptyError(ref file) => write!(f, "File is empty: {}", file),
+            ParserError::CmdError => write!(f, "Error parsing command line args"),
+            ParserError::FileReadError(file) => write!(f, "Unable to read file: {}", file),
+            ParserError::FileEmptyError(file) => write!(f, "File is empty: {}", file),
+            ParserError::EmptySource => write!(f, "Source is empty"),
+            ParserError::SourceTooLarge(width, height) => write!(f, "Source is {}x{}, which doesn't fit in the 80x25 Befunge-93 grid", width, height),
 
-            &ParserError::UnexpectedChar(x, y, c) => {
-                try!(write!(f, "Unexpected char at ({}, {}): {}\n", x, y, c));
-                try!(write!(f, "This may be because of a 'j' or other reason\n"));
+            ParserError::UnexpectedChar(x, y, c) => {
+                writeln!(f, "Unexpected char at ({}, {}): {}", x, y, c)?;
+                writeln!(f, "This may be because of a 'j' or other reason")?;
                 write!(f, "Try passing '--exit-on-invalid' to ignore this")
             }
 
-            &ParserError::VarsDisabled => {
-                try!(write!(f, "Using 'p' or 'g' is disabled by default.\n"));
-                try!(write!(f, "Pass '--enable-vars' to enable using them for variables.\n"));
-                write!(f, "Disabled by default as it potentially allows invalid code.")
+            ParserError::UnsupportedInstruction(c, language) => write!(f, "Instruction '{}' does not exist in {}", c, language),
+
+            ParserError::VarsDisabled => {
+                writeln!(f, "Using 'p' or 'g' is disabled by default.")?;
+                writeln!(f, "Pass '--enable-vars' to enable using them to modify the program.")?;
+                write!(f, "Disabled by default as self-modifying code requires embedding an interpreter.")
             }
 
-            &ParserError::OutputError => write!(f, "Unable to write output"),
-            &ParserError::OutputFileError(ref file) => write!(f, "Unable to open output file for writing: {}", file)
+            ParserError::OutputError => write!(f, "Unable to write output"),
+            ParserError::OutputFileError(file) => write!(f, "Unable to open output file for writing: {}", file),
+            ParserError::UnsupportedAction(target, action) => write!(f, "Unable to generate {} for action: {:?}", target, action)
         }
     }
 }
+
+impl Error for ParserError {}
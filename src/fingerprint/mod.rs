@@ -0,0 +1,393 @@
+use std::io::{self, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::action::Action;
+
+pub trait Fingerprint {
+    fn name(&self) -> &'static str;
+
+    // Every letter loading the fingerprint pushes a semantic for, including ones that just reflect
+    fn letters(&self) -> &'static str;
+
+    // What a letter does, or None if it reflects
+    fn actions(&self, letter: char) -> Option<Vec<Action>>;
+
+    // Helpers behind any `Action::Call`s in this fingerprint's actions, returning false to reflect
+    fn write_impl_to(&self, _writer: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Runs an `Action::Call` in the interpreter for the running IP, returning false to reflect
+    fn call(&self, _name: &str, _stack: &mut Vec<i64>, _context: &mut Context) -> bool {
+        true
+    }
+
+    fn id(&self) -> i64 {
+        id(self.name())
+    }
+}
+
+// What fingerprints keep for each IP between calls, copied by 't' like the rest of the IP
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    // The HRTI timer mark
+    pub mark: Option<Instant>
+}
+
+// Reported by 'y' unless the compiler is given another handprint
+pub const HANDPRINT: &str = "RBEF";
+
+// Packs a name into a cell the way '(' builds fingerprint ids
+pub fn id(name: &str) -> i64 {
+    name.bytes().fold(0, |id, b| id.wrapping_mul(256).wrapping_add(b as i64))
+}
+
+pub fn registry() -> Vec<Box<dyn Fingerprint>> {
+    vec![Box::new(Null), Box::new(Roma), Box::new(Modu), Box::new(Bool), Box::new(Hrti)]
+}
+
+// Actions whose helpers the fingerprints in the registry call into
+pub fn required_actions() -> Vec<Action> {
+    let mut actions = vec![
+        Action::LoadFingerprint(0, 0),
+        Action::UnloadFingerprint(0, 0),
+        Action::Semantic('A', 0, 0)
+    ];
+
+    for fingerprint in registry() {
+        for letter in fingerprint.letters().chars() {
+            for action in fingerprint.actions(letter).unwrap_or_default() {
+                actions.push(match action {
+                    Action::PushNumber(_) => Action::PushNumber(0),
+                    Action::PushChar(_) => Action::PushChar(' '),
+                    a => a
+                })
+            }
+        }
+    }
+
+    actions
+}
+
+pub fn write_runtime<W: Write>(writer: &mut W) -> io::Result<()> {
+    let registry = registry();
+
+    writeln!(writer)?;
+    writeln!(writer, "    fn fingerprint_id(&mut self) -> i64 {{")?;
+    writeln!(writer, "        let n = self.stack.pop().unwrap_or(0);")?;
+    writeln!(writer, "        (0..n).fold(0i64, |id, _| id.wrapping_mul(256).wrapping_add(self.stack.pop().unwrap_or(0)))")?;
+    writeln!(writer, "    }}")?;
+
+    writeln!(writer)?;
+    writeln!(writer, "    fn fingerprint_letters(id: i64) -> Option<&'static str> {{")?;
+    writeln!(writer, "        if !FINGERPRINTS.contains(&id) {{")?;
+    writeln!(writer, "            return None;")?;
+    writeln!(writer, "        }}")?;
+    writeln!(writer)?;
+    writeln!(writer, "        match id {{")?;
+    for fingerprint in registry.iter() {
+        writeln!(writer, "            {:#x} => Some({:?}),", fingerprint.id(), fingerprint.letters())?;
+    }
+    writeln!(writer, "            _ => None")?;
+    writeln!(writer, "        }}")?;
+    writeln!(writer, "    }}")?;
+
+    writeln!(writer)?;
+    writeln!(writer, "    fn load_fingerprint(&mut self) -> bool {{")?;
+    writeln!(writer, "        let id = self.fingerprint_id();")?;
+    writeln!(writer, "        let letters = match Program::fingerprint_letters(id) {{")?;
+    writeln!(writer, "            Some(letters) => letters,")?;
+    writeln!(writer, "            None => return false")?;
+    writeln!(writer, "        }};")?;
+    writeln!(writer)?;
+    writeln!(writer, "        for letter in letters.bytes() {{")?;
+    writeln!(writer, "            self.semantics[(letter - b'A') as usize].push(id);")?;
+    writeln!(writer, "        }}")?;
+    writeln!(writer)?;
+    writeln!(writer, "        self.stack.push(id);")?;
+    writeln!(writer, "        self.stack.push(1);")?;
+    writeln!(writer, "        true")?;
+    writeln!(writer, "    }}")?;
+
+    writeln!(writer)?;
+    writeln!(writer, "    fn unload_fingerprint(&mut self) -> bool {{")?;
+    writeln!(writer, "        let letters = match Program::fingerprint_letters(self.fingerprint_id()) {{")?;
+    writeln!(writer, "            Some(letters) => letters,")?;
+    writeln!(writer, "            None => return false")?;
+    writeln!(writer, "        }};")?;
+    writeln!(writer)?;
+    writeln!(writer, "        for letter in letters.bytes() {{")?;
+    writeln!(writer, "            self.semantics[(letter - b'A') as usize].pop();")?;
+    writeln!(writer, "        }}")?;
+    writeln!(writer)?;
+    writeln!(writer, "        true")?;
+    writeln!(writer, "    }}")?;
+
+    writeln!(writer)?;
+    writeln!(writer, "    fn semantic(&mut self, letter: char) -> bool {{")?;
+    writeln!(writer, "        match (self.semantics[letter as usize - 'A' as usize].last().copied(), letter) {{")?;
+    for fingerprint in registry.iter() {
+        for letter in fingerprint.letters().chars() {
+            if let Some(actions) = fingerprint.actions(letter) {
+                let mut body = Vec::new();
+                for action in actions.iter() {
+                    action.write_to(&mut body)?;
+                }
+
+                writeln!(writer, "            (Some({:#x}), {:?}) => {{", fingerprint.id(), letter)?;
+                for line in String::from_utf8_lossy(&body).lines() {
+                    writeln!(writer, "        {}", line)?;
+                }
+                writeln!(writer, "            }},")?;
+            }
+        }
+    }
+    writeln!(writer, "            _ => return false")?;
+    writeln!(writer, "        }}")?;
+    writeln!(writer)?;
+    writeln!(writer, "        true")?;
+    writeln!(writer, "    }}")?;
+
+    for fingerprint in registry.iter() {
+        fingerprint.write_impl_to(writer)?;
+    }
+
+    Ok(())
+}
+
+// Every letter reflects, hiding whatever was loaded underneath
+struct Null;
+
+impl Fingerprint for Null {
+    fn name(&self) -> &'static str {
+        "NULL"
+    }
+
+    fn letters(&self) -> &'static str {
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+    }
+
+    fn actions(&self, _letter: char) -> Option<Vec<Action>> {
+        None
+    }
+}
+
+// Roman numerals push their values
+struct Roma;
+
+impl Fingerprint for Roma {
+    fn name(&self) -> &'static str {
+        "ROMA"
+    }
+
+    fn letters(&self) -> &'static str {
+        "CDILMVX"
+    }
+
+    fn actions(&self, letter: char) -> Option<Vec<Action>> {
+        let value = match letter {
+            'C' => 100,
+            'D' => 500,
+            'I' => 1,
+            'L' => 50,
+            'M' => 1000,
+            'V' => 5,
+            'X' => 10,
+            _ => return None
+        };
+
+        Some(vec![Action::PushNumber(value)])
+    }
+}
+
+// Bitwise logic
+struct Bool;
+
+impl Fingerprint for Bool {
+    fn name(&self) -> &'static str {
+        "BOOL"
+    }
+
+    fn letters(&self) -> &'static str {
+        "ANOX"
+    }
+
+    fn actions(&self, letter: char) -> Option<Vec<Action>> {
+        match letter {
+            'A' => Some(vec![Action::Call("bool_and")]),
+            'N' => Some(vec![Action::Call("bool_not")]),
+            'O' => Some(vec![Action::Call("bool_or")]),
+            'X' => Some(vec![Action::Call("bool_xor")]),
+            _ => None
+        }
+    }
+
+    fn write_impl_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        for &(name, op) in [("bool_and", "&"), ("bool_or", "|"), ("bool_xor", "^")].iter() {
+            writeln!(writer)?;
+            writeln!(writer, "    fn {}(&mut self) -> bool {{", name)?;
+            writeln!(writer, "        let (a, b) = (self.stack.pop().unwrap_or(0), self.stack.pop().unwrap_or(0));")?;
+            writeln!(writer, "        self.stack.push(b {} a);", op)?;
+            writeln!(writer, "        true")?;
+            writeln!(writer, "    }}")?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "    fn bool_not(&mut self) -> bool {{")?;
+        writeln!(writer, "        let a = self.stack.pop().unwrap_or(0);")?;
+        writeln!(writer, "        self.stack.push(!a);")?;
+        writeln!(writer, "        true")?;
+        writeln!(writer, "    }}")
+    }
+
+    fn call(&self, name: &str, stack: &mut Vec<i64>, _context: &mut Context) -> bool {
+        let a = stack.pop().unwrap_or(0);
+
+        if name == "bool_not" {
+            stack.push(!a);
+            return true
+        }
+
+        let b = stack.pop().unwrap_or(0);
+        stack.push(match name {
+            "bool_and" => b & a,
+            "bool_or" => b | a,
+            _ => b ^ a
+        });
+
+        true
+    }
+}
+
+// Modulo with the sign of the divisor, the absolute remainder and the remainder as C has it
+struct Modu;
+
+impl Fingerprint for Modu {
+    fn name(&self) -> &'static str {
+        "MODU"
+    }
+
+    fn letters(&self) -> &'static str {
+        "MUR"
+    }
+
+    fn actions(&self, letter: char) -> Option<Vec<Action>> {
+        match letter {
+            'M' => Some(vec![Action::Call("modu_floor")]),
+            'U' => Some(vec![Action::Call("modu_unsigned")]),
+            'R' => Some(vec![Action::Call("modu_remainder")]),
+            _ => None
+        }
+    }
+
+    fn write_impl_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let ops = [
+            ("modu_floor", "{ let r = b.wrapping_rem(a); if r != 0 && (r < 0) != (a < 0) { r + a } else { r } }"),
+            ("modu_unsigned", "b.wrapping_rem(a).wrapping_abs()"),
+            ("modu_remainder", "b.wrapping_rem(a)")
+        ];
+
+        for &(name, op) in ops.iter() {
+            writeln!(writer)?;
+            writeln!(writer, "    fn {}(&mut self) -> bool {{", name)?;
+            writeln!(writer, "        let (a, b) = (self.stack.pop().unwrap_or(0), self.stack.pop().unwrap_or(0));")?;
+            writeln!(writer, "        self.stack.push(if a == 0 {{ 0 }} else {{ {} }});", op)?;
+            writeln!(writer, "        true")?;
+            writeln!(writer, "    }}")?;
+        }
+
+        Ok(())
+    }
+
+    fn call(&self, name: &str, stack: &mut Vec<i64>, _context: &mut Context) -> bool {
+        let (a, b) = (stack.pop().unwrap_or(0), stack.pop().unwrap_or(0));
+
+        stack.push(match name {
+            _ if a == 0 => 0,
+            "modu_floor" => match b.wrapping_rem(a) {
+                r if r != 0 && (r < 0) != (a < 0) => r + a,
+                r => r
+            },
+            "modu_unsigned" => b.wrapping_rem(a).wrapping_abs(),
+            _ => b.wrapping_rem(a)
+        });
+
+        true
+    }
+}
+
+// A microsecond timer with a mark for each IP
+struct Hrti;
+
+impl Fingerprint for Hrti {
+    fn name(&self) -> &'static str {
+        "HRTI"
+    }
+
+    fn letters(&self) -> &'static str {
+        "EGMST"
+    }
+
+    fn actions(&self, letter: char) -> Option<Vec<Action>> {
+        match letter {
+            'G' => Some(vec![Action::PushNumber(1)]),
+            'E' => Some(vec![Action::Call("hrti_erase")]),
+            'M' => Some(vec![Action::Call("hrti_mark")]),
+            'S' => Some(vec![Action::Call("hrti_second")]),
+            'T' => Some(vec![Action::Call("hrti_elapsed")]),
+            _ => None
+        }
+    }
+
+    fn write_impl_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        writeln!(writer, "{}", HRTI)
+    }
+
+    fn call(&self, name: &str, stack: &mut Vec<i64>, context: &mut Context) -> bool {
+        match name {
+            "hrti_erase" => context.mark = None,
+            "hrti_mark" => context.mark = Some(Instant::now()),
+
+            "hrti_second" => {
+                let micros = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_micros());
+                stack.push(micros as i64)
+            },
+
+            _ => match context.mark {
+                Some(mark) => stack.push(mark.elapsed().as_micros() as i64),
+                None => return false
+            }
+        }
+
+        true
+    }
+}
+
+const HRTI: &str = r#"
+    fn hrti_erase(&mut self) -> bool {
+        self.mark = None;
+        true
+    }
+
+    fn hrti_mark(&mut self) -> bool {
+        self.mark = Some(std::time::Instant::now());
+        true
+    }
+
+    fn hrti_second(&mut self) -> bool {
+        let micros = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_micros());
+
+        self.stack.push(micros as i64);
+        true
+    }
+
+    fn hrti_elapsed(&mut self) -> bool {
+        match self.mark {
+            Some(mark) => self.stack.push(mark.elapsed().as_micros() as i64),
+            None => return false
+        }
+
+        true
+    }"#;
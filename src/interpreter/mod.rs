@@ -0,0 +1,894 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::env;
+use std::fs;
+use std::io::{BufReader, Bytes, Read, Write};
+use std::mem;
+use std::path::MAIN_SEPARATOR;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ip::IP;
+use crate::action::Action;
+use crate::runtime;
+use crate::fingerprint::{self, Context, Fingerprint};
+use crate::error::ParserError;
+use crate::parser::Standard;
+
+// Same xorshift64* generator the compiled programs embed for '?'
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Rng {
+        Rng(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+}
+
+// An IP waiting for its turn, holding everything 't' gives each IP its own copy of
+struct Thread {
+    id: i64,
+    ip: IP,
+    stack: Vec<i64>,
+    stacks: Vec<Vec<i64>>,
+    offset: (i64, i64, i64),
+    semantics: Vec<Vec<i64>>,
+    context: Context,
+    stringmode: bool,
+    iterate: Option<(i64, i64)>
+}
+
+pub struct Interpreter<R, W> {
+    code: Vec<Vec<Vec<i64>>>,
+    table: HashMap<(i64, i64, i64), i64>,
+    stack: Vec<i64>,
+    stacks: Vec<Vec<i64>>,
+    offset: (i64, i64, i64),
+    semantics: Vec<Vec<i64>>,
+    context: Context,
+    fingerprints: Vec<Box<dyn Fingerprint>>,
+    handprint: i64,
+    exit_code: i64,
+    id: i64,
+    next_id: i64,
+    threads: VecDeque<Thread>,
+    ip: IP,
+    width: usize,
+    height: usize,
+    depth: usize,
+    dimensions: usize,
+    std: Standard,
+    stringmode: bool,
+    iterate: Option<(i64, i64)>,
+    vars_enabled: bool,
+    execute_enabled: bool,
+    file_io_enabled: bool,
+    exit_on_invalid: bool,
+    rng: Rng,
+    input: Bytes<BufReader<R>>,
+    output: W
+}
+
+impl<R: Read, W: Write> Interpreter<R, W> {
+    pub fn new(code: &[Vec<Vec<char>>], vars: bool, inv: bool, input: R, output: W) -> Interpreter<R, W> {
+        Interpreter {
+            code: code.iter().map(|plane| plane.iter().map(|row| row.iter().map(|c| *c as i64).collect()).collect()).collect(),
+            table: HashMap::new(),
+            stack: Vec::new(),
+            stacks: Vec::new(),
+            offset: (0, 0, 0),
+            semantics: vec![Vec::new(); 26],
+            context: Context::default(),
+            fingerprints: fingerprint::registry(),
+            handprint: fingerprint::id(fingerprint::HANDPRINT),
+            exit_code: 0,
+            id: 0,
+            next_id: 0,
+            threads: VecDeque::new(),
+            ip: IP::new(0, 0, 1, 0),
+            width: code[0][0].len(),
+            height: code[0].len(),
+            depth: code.len(),
+            dimensions: 2,
+            std: Standard::Funge98,
+            stringmode: false,
+            iterate: None,
+            vars_enabled: vars,
+            execute_enabled: false,
+            file_io_enabled: false,
+            exit_on_invalid: inv,
+            rng: Rng::new(),
+            input: BufReader::new(input).bytes(),
+            output
+        }
+    }
+
+    pub fn handprint(mut self, handprint: i64) -> Interpreter<R, W> {
+        self.handprint = handprint;
+        self
+    }
+
+    // Limits '(' to the fingerprints with these ids
+    pub fn fingerprints(mut self, ids: &[i64]) -> Interpreter<R, W> {
+        self.fingerprints.retain(|fingerprint| ids.contains(&fingerprint.id()));
+        self
+    }
+
+    // Which of h, l and m exist and how many components vectors have on the stack
+    pub fn dimensions(mut self, dimensions: usize) -> Interpreter<R, W> {
+        self.dimensions = dimensions;
+        self
+    }
+
+    pub fn std(mut self, std: Standard) -> Interpreter<R, W> {
+        self.std = std;
+        self
+    }
+
+    pub fn enable_execute(mut self, enabled: bool) -> Interpreter<R, W> {
+        self.execute_enabled = enabled;
+        self
+    }
+
+    pub fn enable_file_io(mut self, enabled: bool) -> Interpreter<R, W> {
+        self.file_io_enabled = enabled;
+        self
+    }
+
+    pub fn run(&mut self) -> Result<(), ParserError> {
+        loop {
+            // Spaces and comments take no time, so every tick runs an actual instruction
+            if !self.stringmode && self.iterate.is_none() {
+                self.skip_free()
+            }
+
+            let alive = !self.step()?;
+
+            if alive {
+                match self.iterate {
+                    Some((c, n)) if n > 0 => self.iterate = Some((c, n - 1)),
+
+                    _ => {
+                        self.iterate = None;
+                        self.ip.advance(self.width, self.height, self.depth)
+                    }
+                }
+            }
+
+            // Each IP runs a single instruction per tick, in the order they were queued
+            let mut next = match self.threads.pop_front() {
+                Some(next) => next,
+                None if alive => continue,
+                None => return self.output.flush().map_err(|_| ParserError::OutputError)
+            };
+
+            self.switch(&mut next);
+
+            if alive {
+                self.threads.push_back(next)
+            }
+        }
+    }
+
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    // The code given to 'q', or 0 if the program ended with '@'
+    pub fn exit_code(&self) -> i64 {
+        self.exit_code
+    }
+
+    // Executes the instruction under the IP, or the one being repeated by 'k',
+    // returning true once the program has ended
+    fn step(&mut self) -> Result<bool, ParserError> {
+        let c = match self.iterate {
+            Some((c, _)) => c,
+            None => self.cell(self.ip.x, self.ip.y, self.ip.z)
+        };
+
+        if self.stringmode {
+            if c == '"' as i64 {
+                self.stringmode = false
+            } else {
+                self.stack.push(c)
+            }
+
+            return Ok(false)
+        }
+
+        match u32::try_from(c).ok().and_then(char::from_u32).unwrap_or(' ') {
+            // Instructions the standard doesn't have are invalid characters, so they're only ignored with exit_on_invalid
+            c if !self.std.supports(c) => if !self.exit_on_invalid {
+                return Err(ParserError::UnsupportedInstruction(c, self.std.name().to_string()))
+            },
+
+            c @ ('^' | 'v' | '|' | '[' | ']' | 'w') if self.dimensions == 1 => if !self.exit_on_invalid {
+                return Err(ParserError::UnsupportedInstruction(c, "Unefunge-98".to_string()))
+            },
+
+            '>' => self.ip.right(),
+            '<' => self.ip.left(),
+            '^' => self.ip.up(),
+            'v' => self.ip.down(),
+            '[' => self.ip.rotate_left(),
+            ']' => self.ip.rotate_right(),
+            'r' => self.ip.flip(),
+            'h' if self.dimensions > 2 => self.ip.high(),
+            'l' if self.dimensions > 2 => self.ip.low(),
+            '#' => self.ip.advance(self.width, self.height, self.depth),
+            '"' => self.stringmode = true,
+
+            ';' => loop {
+                self.ip.advance(self.width, self.height, self.depth);
+
+                if self.cell(self.ip.x, self.ip.y, self.ip.z) == ';' as i64 {
+                    break
+                }
+            },
+
+            d @ '0'..='9' => self.stack.push(d.to_digit(10).unwrap() as i64),
+            h @ 'a'..='f' => self.stack.push(h.to_digit(16).unwrap() as i64),
+
+            '~' => {
+                let c = self.read_char().map_or(-1, |c| c as i64);
+                self.stack.push(c)
+            },
+
+            '&' => {
+                let n = self.input_number();
+                self.stack.push(n)
+            },
+
+            ',' => {
+                let c = u32::try_from(self.pop()).ok().and_then(char::from_u32).unwrap_or('\0');
+                write!(self.output, "{}", c)
+                    .and_then(|_| self.output.flush())
+                    .map_err(|_| ParserError::OutputError)?
+            },
+
+            '.' => {
+                let n = self.pop();
+                write!(self.output, "{}", n).map_err(|_| ParserError::OutputError)?
+            },
+
+            '+' => self.binary(|a, b| b.wrapping_add(a)),
+            '-' => self.binary(|a, b| b.wrapping_sub(a)),
+            '*' => self.binary(|a, b| b.wrapping_mul(a)),
+            '/' => self.binary(|a, b| if a == 0 { 0 } else { b.wrapping_div(a) }),
+            '%' => self.binary(|a, b| if a == 0 { 0 } else { b.wrapping_rem(a) }),
+            '`' => self.binary(|a, b| if b > a { 1 } else { 0 }),
+
+            '!' => {
+                let n = self.pop();
+                self.stack.push(if n == 0 { 1 } else { 0 })
+            },
+
+            ':' => {
+                let n = self.pop();
+                self.stack.push(n);
+                self.stack.push(n)
+            },
+
+            '\\' => {
+                let (a, b) = (self.pop(), self.pop());
+                self.stack.push(a);
+                self.stack.push(b)
+            },
+
+            '$' => { self.stack.pop(); },
+            'n' => self.stack.clear(),
+
+            '\'' => {
+                self.ip.advance(self.width, self.height, self.depth);
+                let c = self.cell(self.ip.x, self.ip.y, self.ip.z);
+                self.stack.push(c)
+            },
+
+            '?' => match self.rng.next() % (2 * self.dimensions as u64) {
+                0 => self.ip.right(),
+                1 => self.ip.left(),
+                2 => self.ip.down(),
+                3 => self.ip.up(),
+                4 => self.ip.high(),
+                _ => self.ip.low()
+            },
+
+            'j' => {
+                let n = self.pop();
+                self.ip.jump(n, self.width, self.height, self.depth)
+            },
+
+            '_' => if self.pop() == 0 { self.ip.right() } else { self.ip.left() },
+            '|' => if self.pop() == 0 { self.ip.down() } else { self.ip.up() },
+            'm' if self.dimensions > 2 => if self.pop() == 0 { self.ip.low() } else { self.ip.high() },
+
+            'w' => {
+                let (a, b) = (self.pop(), self.pop());
+
+                if a < b {
+                    self.ip.rotate_left()
+                } else if a > b {
+                    self.ip.rotate_right()
+                }
+            },
+
+            'k' => {
+                let n = self.pop();
+
+                loop {
+                    self.ip.advance(self.width, self.height, self.depth);
+
+                    match u32::try_from(self.cell(self.ip.x, self.ip.y, self.ip.z)).ok().and_then(char::from_u32) {
+                        Some(' ') => (),
+
+                        Some(';') => loop {
+                            self.ip.advance(self.width, self.height, self.depth);
+
+                            if self.cell(self.ip.x, self.ip.y, self.ip.z) == ';' as i64 {
+                                break
+                            }
+                        },
+
+                        _ => break
+                    }
+                }
+
+                if n > 0 {
+                    self.iterate = Some((self.cell(self.ip.x, self.ip.y, self.ip.z), n))
+                }
+            },
+
+            'x' => (self.ip.dx, self.ip.dy, self.ip.dz) = self.pop_vector(),
+
+            '{' => {
+                let next = self.ip.new_straight(self.width, self.height, self.depth);
+                self.begin_block((next.x, next.y, next.z))
+            },
+
+            '}' => if !self.end_block() { self.ip.flip() },
+            'u' => if !self.under() { self.ip.flip() },
+
+            '(' => if !self.load_fingerprint() { self.ip.flip() },
+            ')' => if !self.unload_fingerprint() { self.ip.flip() },
+            c @ 'A'..='Z' => if !self.semantic(c) { self.ip.flip() },
+
+            // Befunge-93 has nothing outside its grid, so writes there are dropped and reads give 0
+            'p' if self.vars_enabled => {
+                let (x, y, z) = self.pop_vector();
+                let v = self.pop();
+                let (x, y, z) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));
+
+                if self.std == Standard::Funge98 || self.in_grid(x, y, z) {
+                    self.put(x, y, z, v)
+                }
+            },
+
+            'g' if self.vars_enabled => {
+                let (x, y, z) = self.pop_vector();
+                let (x, y, z) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));
+                let c = if self.std == Standard::Funge98 || self.in_grid(x, y, z) { self.cell(x, y, z) } else { 0 };
+                self.stack.push(c)
+            },
+
+            's' if self.vars_enabled => {
+                self.ip.advance(self.width, self.height, self.depth);
+                let v = self.pop();
+                self.put(self.ip.x, self.ip.y, self.ip.z, v)
+            },
+
+            'p' | 'g' | 's' => return Err(ParserError::VarsDisabled),
+
+            'z' => (),
+            'y' => self.system_info(),
+
+            't' => {
+                let mut ip = self.ip;
+                ip.flip();
+                ip.advance(self.width, self.height, self.depth);
+
+                self.next_id += 1;
+                self.threads.push_back(Thread {
+                    id: self.next_id,
+                    ip,
+                    stack: self.stack.clone(),
+                    stacks: self.stacks.clone(),
+                    offset: self.offset,
+                    semantics: self.semantics.clone(),
+                    context: self.context.clone(),
+                    stringmode: false,
+                    iterate: None
+                })
+            },
+
+            '@' => return Ok(true),
+
+            '=' => {
+                let status = self.execute()?;
+                self.stack.push(status)
+            },
+
+            'i' => if !self.file_input() { self.ip.flip() },
+            'o' => if !self.file_output() { self.ip.flip() },
+
+            'q' => {
+                self.exit_code = self.pop();
+                self.threads.clear();
+                return Ok(true)
+            },
+            ' ' => (),
+
+            c => if !self.exit_on_invalid {
+                return Err(ParserError::UnexpectedChar(self.ip.x, self.ip.y, c))
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Swaps the running IP with a waiting one
+    fn switch(&mut self, thread: &mut Thread) {
+        mem::swap(&mut self.id, &mut thread.id);
+        mem::swap(&mut self.ip, &mut thread.ip);
+        mem::swap(&mut self.stack, &mut thread.stack);
+        mem::swap(&mut self.stacks, &mut thread.stacks);
+        mem::swap(&mut self.offset, &mut thread.offset);
+        mem::swap(&mut self.semantics, &mut thread.semantics);
+        mem::swap(&mut self.context, &mut thread.context);
+        mem::swap(&mut self.stringmode, &mut thread.stringmode);
+        mem::swap(&mut self.iterate, &mut thread.iterate)
+    }
+
+    fn pop(&mut self) -> i64 {
+        self.stack.pop().unwrap_or(0)
+    }
+
+    // Vectors on the stack only have as many components as there are dimensions
+    fn pop_vector(&mut self) -> (i64, i64, i64) {
+        let z = if self.dimensions > 2 { self.pop() } else { 0 };
+        let y = if self.dimensions > 1 { self.pop() } else { 0 };
+        (self.pop(), y, z)
+    }
+
+    fn push_vector(&mut self, (x, y, z): (i64, i64, i64)) {
+        self.stack.extend([x, y, z].iter().take(self.dimensions))
+    }
+
+    // How many of n values to move off a stack of the given length, padding past its bottom for only so long
+    fn count(n: u64, len: usize) -> usize {
+        n.min(len as u64 + runtime::MAX_PADDING) as usize
+    }
+
+    // Moves the top n values of one stack onto another, keeping their order and padding with zeroes
+    fn transfer(from: &mut Vec<i64>, to: &mut Vec<i64>, n: u64) {
+        let len = from.len();
+        let n = Self::count(n, len);
+
+        if n > len {
+            to.resize(to.len() + n - len, 0);
+            to.append(from);
+        } else {
+            to.extend(from.drain(len - n..));
+        }
+    }
+
+    fn begin_block(&mut self, next: (i64, i64, i64)) {
+        let n = self.pop();
+        let mut toss = Vec::new();
+
+        if n > 0 {
+            Self::transfer(&mut self.stack, &mut toss, n.unsigned_abs())
+        } else {
+            let len = self.stack.len() + Self::count(n.unsigned_abs(), 0);
+            self.stack.resize(len, 0)
+        }
+
+        self.push_vector(self.offset);
+        self.offset = next;
+
+        let soss = mem::replace(&mut self.stack, toss);
+        self.stacks.push(soss)
+    }
+
+    fn end_block(&mut self) -> bool {
+        let soss = match self.stacks.pop() {
+            Some(soss) => soss,
+            None => return false
+        };
+
+        let n = self.pop();
+        let mut toss = mem::replace(&mut self.stack, soss);
+        self.offset = self.pop_vector();
+
+        if n > 0 {
+            Self::transfer(&mut toss, &mut self.stack, n.unsigned_abs())
+        } else {
+            let len = self.stack.len().saturating_sub(Self::count(n.unsigned_abs(), self.stack.len()));
+            self.stack.truncate(len)
+        }
+
+        true
+    }
+
+    fn under(&mut self) -> bool {
+        let soss = match self.stacks.last_mut() {
+            Some(soss) => soss,
+            None => return false
+        };
+
+        let n = self.stack.pop().unwrap_or(0);
+
+        if n > 0 {
+            for _ in 0..Self::count(n.unsigned_abs(), soss.len()) {
+                self.stack.push(soss.pop().unwrap_or(0))
+            }
+        } else {
+            for _ in 0..Self::count(n.unsigned_abs(), self.stack.len()) {
+                soss.push(self.stack.pop().unwrap_or(0))
+            }
+        }
+
+        true
+    }
+
+    // Pops a count and that many values into a fingerprint id, returning the fingerprint's index
+    fn fingerprint(&mut self) -> Option<(usize, i64)> {
+        let n = self.pop();
+        let id = (0..n).fold(0i64, |id, _| id.wrapping_mul(256).wrapping_add(self.pop()));
+        self.fingerprints.iter().position(|f| f.id() == id).map(|index| (index, id))
+    }
+
+    fn load_fingerprint(&mut self) -> bool {
+        let (index, id) = match self.fingerprint() {
+            Some(fingerprint) => fingerprint,
+            None => return false
+        };
+
+        for letter in self.fingerprints[index].letters().bytes() {
+            self.semantics[(letter - b'A') as usize].push(id)
+        }
+
+        self.stack.push(id);
+        self.stack.push(1);
+        true
+    }
+
+    fn unload_fingerprint(&mut self) -> bool {
+        let index = match self.fingerprint() {
+            Some((index, _)) => index,
+            None => return false
+        };
+
+        for letter in self.fingerprints[index].letters().bytes() {
+            self.semantics[(letter - b'A') as usize].pop();
+        }
+
+        true
+    }
+
+    fn semantic(&mut self, letter: char) -> bool {
+        let id = match self.semantics[letter as usize - 'A' as usize].last() {
+            Some(&id) => id,
+            None => return false
+        };
+
+        let fingerprint = match self.fingerprints.iter().find(|f| f.id() == id) {
+            Some(fingerprint) => fingerprint,
+            None => return false
+        };
+
+        let actions = match fingerprint.actions(letter) {
+            Some(actions) => actions,
+            None => return false
+        };
+
+        for action in actions {
+            match action {
+                Action::PushNumber(n) => self.stack.push(n),
+                Action::PushChar(c) => self.stack.push(c as i64),
+                Action::Call(name) => if !fingerprint.call(name, &mut self.stack, &mut self.context) {
+                    return false
+                },
+                a => unreachable!("fingerprint action {:?} is not supported by the interpreter", a)
+            }
+        }
+
+        true
+    }
+
+    fn system_info(&mut self) {
+        let n = self.pop();
+        let len = self.stack.len();
+
+        self.stack.push(0);
+        for (key, value) in env::vars().collect::<Vec<_>>().iter().rev() {
+            Self::push_string(&mut self.stack, &format!("{}={}", key, value))
+        }
+
+        self.stack.push(0);
+        for arg in env::args().collect::<Vec<_>>().iter().rev() {
+            Self::push_string(&mut self.stack, arg)
+        }
+
+        for i in 0..self.stacks.len() {
+            let size = self.stacks[i].len() as i64;
+            self.stack.push(size)
+        }
+
+        self.stack.push(len as i64);
+        self.stack.push(self.stacks.len() as i64 + 1);
+
+        let (date, time) = date_time();
+        self.stack.push(time);
+        self.stack.push(date);
+
+        let ((lx, ly, lz), (gx, gy, gz)) = self.bounds();
+        self.push_vector((gx - lx, gy - ly, gz - lz));
+        self.push_vector((lx, ly, lz));
+
+        self.push_vector(self.offset);
+        self.push_vector(self.ip.delta());
+        self.push_vector((self.ip.x, self.ip.y, self.ip.z));
+        let flags = 0b0001 | if self.execute_enabled { 0b1000 } else { 0 } | if self.file_io_enabled { 0b0110 } else { 0 };
+        self.stack.extend([0, self.id, self.dimensions as i64, MAIN_SEPARATOR as i64, self.execute_enabled as i64, runtime::version(), self.handprint, 8, flags]);
+
+        if n > 0 {
+            let value = self.stack.len().checked_sub(n as usize).map_or(0, |i| self.stack[i]);
+            self.stack.truncate(len);
+            self.stack.push(value)
+        }
+    }
+
+    // Pops a string and runs it through the shell, returning its exit status or -1
+    fn execute(&mut self) -> Result<i64, ParserError> {
+        let command = self.pop_string();
+
+        if !self.execute_enabled {
+            return Ok(-1)
+        }
+
+        self.output.flush().map_err(|_| ParserError::OutputError)?;
+
+        let status = if cfg!(windows) {
+            Command::new("cmd").arg("/C").arg(&command).status()
+        } else {
+            Command::new("sh").arg("-c").arg(&command).status()
+        };
+
+        Ok(status.ok().and_then(|status| status.code()).map_or(-1, |code| code as i64))
+    }
+
+    // Loads a file into Funge-space, as a single line in binary mode, pushing its size and where it went
+    fn file_input(&mut self) -> bool {
+        let name = self.pop_string();
+        let flags = self.pop();
+        let (x, y, z) = self.pop_vector();
+
+        if !self.file_io_enabled {
+            return false
+        }
+
+        let data = match fs::read(&name) {
+            Ok(data) => data,
+            Err(_) => return false
+        };
+
+        let (ox, oy, oz) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));
+        let (mut width, mut height, mut depth) = (0, 0, 0);
+
+        if flags & 1 != 0 {
+            for (i, &b) in data.iter().enumerate() {
+                self.put(ox + i as i64, oy, oz, b as i64)
+            }
+
+            width = data.len() as i64;
+            height = if data.is_empty() { 0 } else { 1 };
+            depth = height;
+        } else {
+            // Unefunge ignores line breaks, and form feeds only separate planes in Trefunge
+            let data: Vec<u8> = if self.dimensions > 1 { data } else { data.into_iter().filter(|&b| b != b'\n' && b != b'\r').collect() };
+            let planes: Vec<&[u8]> = if self.dimensions > 2 { data.split(|&b| b == b'\x0c').collect() } else { vec![&data] };
+
+            for (plane, data) in planes.into_iter().enumerate() {
+                let data = data.strip_suffix(b"\n").unwrap_or(data);
+
+                for (row, line) in data.split(|&b| b == b'\n').enumerate() {
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+                    for (i, &b) in line.iter().enumerate() {
+                        if b != b' ' {
+                            self.put(ox + i as i64, oy + row as i64, oz + plane as i64, b as i64)
+                        }
+                    }
+
+                    width = width.max(line.len() as i64);
+                    height = height.max(row as i64 + 1);
+                }
+
+                depth = plane as i64 + 1;
+            }
+        }
+
+        self.push_vector((width, height, depth));
+        self.push_vector((x, y, z));
+        true
+    }
+
+    // Writes a box of Funge-space to a file, trimming it as linear text if asked
+    fn file_output(&mut self) -> bool {
+        let name = self.pop_string();
+        let flags = self.pop();
+        let (x, y, z) = self.pop_vector();
+        let (width, height, depth) = self.pop_vector();
+
+        if !self.file_io_enabled {
+            return false
+        }
+
+        let (ox, oy, oz) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));
+        let height = if self.dimensions > 1 { height } else { 1 };
+        let depth = if self.dimensions > 2 { depth } else { 1 };
+
+        let planes: Vec<String> = (0..depth).map(|plane| {
+            let mut lines: Vec<String> = (0..height).map(|row| {
+                (0..width).map(|col| u32::try_from(self.cell(ox + col, oy + row, oz + plane)).ok().and_then(char::from_u32).unwrap_or(' ')).collect()
+            }).collect();
+
+            if flags & 1 != 0 {
+                for line in lines.iter_mut() {
+                    line.truncate(line.trim_end_matches(' ').len())
+                }
+
+                while lines.last().is_some_and(|line| line.is_empty()) {
+                    lines.pop();
+                }
+            }
+
+            lines.iter().map(|line| format!("{}\n", line)).collect()
+        }).collect();
+
+        fs::write(&name, planes.join("\x0c")).is_ok()
+    }
+
+    fn pop_string(&mut self) -> String {
+        let mut s = String::new();
+
+        loop {
+            match self.pop() {
+                0 => return s,
+                c => s.push(u32::try_from(c).ok().and_then(char::from_u32).unwrap_or('\0'))
+            }
+        }
+    }
+
+    // Strings on the stack are null-terminated with their first character on top
+    fn push_string(stack: &mut Vec<i64>, s: &str) {
+        stack.push(0);
+        stack.extend(s.chars().rev().map(|c| c as i64))
+    }
+
+    // The least and greatest points holding something other than a space
+    fn bounds(&self) -> ((i64, i64, i64), (i64, i64, i64)) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let points = (0..self.depth as i64).flat_map(|z| (0..height).flat_map(move |y| (0..width).map(move |x| (x, y, z))))
+            .chain(self.table.keys().copied())
+            .filter(|&(x, y, z)| self.cell(x, y, z) != ' ' as i64);
+
+        points.fold(None, |bounds, (x, y, z)| Some(match bounds {
+            Some(((lx, ly, lz), (gx, gy, gz))) => ((x.min(lx), y.min(ly), z.min(lz)), (x.max(gx), y.max(gy), z.max(gz))),
+            None => ((x, y, z), (x, y, z))
+        })).unwrap_or(((0, 0, 0), (0, 0, 0)))
+    }
+
+    fn binary<F: Fn(i64, i64) -> i64>(&mut self, f: F) {
+        let (a, b) = (self.pop(), self.pop());
+        self.stack.push(f(a, b))
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        let first = self.input.next()?.ok()?;
+        let len = match first {
+            0xf0..=0xf7 => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1
+        };
+
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            bytes.push(self.input.next()?.ok()?);
+        }
+
+        Some(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    fn input_number(&mut self) -> i64 {
+        let mut negative = false;
+        let mut number: Option<i64> = None;
+
+        loop {
+            match self.read_char() {
+                Some(c) if c.is_ascii_digit() => number = Some(number.unwrap_or(0).wrapping_mul(10).wrapping_add(c.to_digit(10).unwrap() as i64)),
+                Some('-') if number.is_none() => negative = true,
+                Some(_) if number.is_none() => negative = false,
+                _ => break
+            }
+        }
+
+        match number {
+            Some(n) if negative => -n,
+            Some(n) => n,
+            None => -1
+        }
+    }
+
+    fn in_grid(&self, x: i64, y: i64, z: i64) -> bool {
+        x >= 0 && y >= 0 && z >= 0 && x < self.width as i64 && y < self.height as i64 && z < self.depth as i64
+    }
+
+    fn cell(&self, x: i64, y: i64, z: i64) -> i64 {
+        if self.in_grid(x, y, z) {
+            self.code[z as usize][y as usize][x as usize]
+        } else {
+            match self.table.get(&(x, y, z)) {
+                Some(value) => *value,
+                None => ' ' as i64
+            }
+        }
+    }
+
+    // Moves the IP past any spaces and ';' comments, giving up after a full lap of the grid in case it only meets empty cells
+    fn skip_free(&mut self) {
+        for _ in 0..self.width * self.height * self.depth {
+            let c = self.cell(self.ip.x, self.ip.y, self.ip.z);
+
+            if c == ';' as i64 {
+                loop {
+                    self.ip.advance(self.width, self.height, self.depth);
+
+                    if self.cell(self.ip.x, self.ip.y, self.ip.z) == ';' as i64 {
+                        break
+                    }
+                }
+            } else if c != ' ' as i64 {
+                return
+            }
+
+            self.ip.advance(self.width, self.height, self.depth)
+        }
+    }
+
+    fn put(&mut self, x: i64, y: i64, z: i64, value: i64) {
+        if self.in_grid(x, y, z) {
+            self.code[z as usize][y as usize][x as usize] = value
+        } else {
+            self.table.insert((x, y, z), value);
+        }
+    }
+}
+
+// The current UTC date and time, packed the way 'y' reports them
+fn date_time() -> (i64, i64) {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+    let (days, time) = (secs / 86400, secs % 86400);
+
+    // Days since the epoch to a civil date in the proleptic Gregorian calendar
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    ((year - 1900) * 256 * 256 + month * 256 + day, time / 3600 * 256 * 256 + time / 60 % 60 * 256 + time % 60)
+}
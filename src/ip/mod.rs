@@ -1,110 +1,170 @@
-#[deriving(Show, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct IP {
-    pub x: int,
-    pub y: int,
-    pub dx: int,
-    pub dy: int
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+    pub dx: i64,
+    pub dy: i64,
+    pub dz: i64
 }
 
 impl IP {
-    pub fn new(x: int, y: int, dx: int, dy: int) -> IP {
+    pub fn new(x: i64, y: i64, dx: i64, dy: i64) -> IP {
         IP {
-            x: x,
-            y: y,
-            dx: dx,
-            dy: dy
+            x,
+            y,
+            z: 0,
+            dx,
+            dy,
+            dz: 0
         }
     }
 
-    pub fn new_straight(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
-        new.advance(width, height);
+    pub fn new_straight(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn new_turn_right(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
+    pub fn new_turn_right(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
         new.rotate_right();
-        new.advance(width, height);
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn new_turn_left(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
+    pub fn new_turn_left(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
         new.rotate_left();
-        new.advance(width, height);
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn new_left(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
+    pub fn new_left(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
         new.left();
-        new.advance(width, height);
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn new_right(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
+    pub fn new_right(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
         new.right();
-        new.advance(width, height);
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn new_up(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
+    pub fn new_up(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
         new.up();
-        new.advance(width, height);
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn new_down(&self, width: uint, height: uint) -> IP {
-        let mut new = self.clone();
+    pub fn new_down(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
         new.down();
-        new.advance(width, height);
+        new.advance(width, height, depth);
         new
     }
 
-    pub fn delta(&self) -> (int, int) {
-        (self.dx, self.dy)
+    pub fn new_high(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
+        new.high();
+        new.advance(width, height, depth);
+        new
+    }
+
+    pub fn new_low(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
+        new.low();
+        new.advance(width, height, depth);
+        new
+    }
+
+    pub fn new_reverse(&self, width: usize, height: usize, depth: usize) -> IP {
+        let mut new = *self;
+        new.flip();
+        new.advance(width, height, depth);
+        new
+    }
+
+    pub fn delta(&self) -> (i64, i64, i64) {
+        (self.dx, self.dy, self.dz)
+    }
+
+    // Lahey-space wrapping: on leaving the grid, backtrack along the delta to the opposite edge
+    pub fn advance(&mut self, width: usize, height: usize, depth: usize) {
+        let in_bounds = |x: i64, y: i64, z: i64| x >= 0 && y >= 0 && z >= 0 && x < width as i64 && y < height as i64 && z < depth as i64;
+
+        if in_bounds(self.x + self.dx, self.y + self.dy, self.z + self.dz) {
+            self.x += self.dx;
+            self.y += self.dy;
+            self.z += self.dz;
+        } else {
+            while in_bounds(self.x - self.dx, self.y - self.dy, self.z - self.dz) {
+                self.x -= self.dx;
+                self.y -= self.dy;
+                self.z -= self.dz;
+            }
+        }
     }
 
-    pub fn advance(&mut self, width: uint, height: uint) {
-        self.x += self.dx as int;
-        self.y += self.dy as int;
+    // Moves n cells along the delta, or back against it for negative n. The path always wraps around to
+    // where it started, so only the count modulo its length needs to be walked.
+    pub fn jump(&mut self, n: i64, width: usize, height: usize, depth: usize) {
+        let start = *self;
+        let mut len = 1;
+        self.advance(width, height, depth);
 
-        if self.x >= width as int {
-            self.x -= width as int
-        } else if self.x < 0 {
-            self.x += width as int
+        while *self != start {
+            self.advance(width, height, depth);
+            len += 1
         }
 
-        if self.y >= height as int {
-            self.y -= height as int
-        } else if self.y < 0 {
-            self.y += height as int
+        for _ in 0..n.rem_euclid(len) {
+            self.advance(width, height, depth)
         }
     }
 
     pub fn left(&mut self) {
         self.dx = -1;
         self.dy = 0;
+        self.dz = 0;
     }
 
     pub fn right(&mut self) {
         self.dx = 1;
         self.dy = 0;
+        self.dz = 0;
     }
 
     pub fn up(&mut self) {
         self.dx = 0;
         self.dy = -1;
+        self.dz = 0;
     }
 
     pub fn down(&mut self) {
         self.dx = 0;
         self.dy = 1;
+        self.dz = 0;
+    }
+
+    // Trefunge only, towards the next plane of the source
+    pub fn high(&mut self) {
+        self.dx = 0;
+        self.dy = 0;
+        self.dz = 1;
     }
 
+    pub fn low(&mut self) {
+        self.dx = 0;
+        self.dy = 0;
+        self.dz = -1;
+    }
+
+    // Turns happen in the xy plane, leaving dz alone
     pub fn rotate_right(&mut self) {
         let new_dx = self.dy;
         let new_dy = -self.dx;
@@ -124,6 +184,6 @@ impl IP {
     pub fn flip(&mut self) {
         self.dx *= -1;
         self.dy *= -1;
+        self.dz *= -1;
     }
 }
-
@@ -0,0 +1,19 @@
+pub use parser::{Parser, Emit, Standard, StateGraph};
+pub use action::Action;
+pub use ip::IP;
+pub use error::ParserError;
+pub use interpreter::Interpreter;
+
+pub mod ip;
+pub mod action;
+pub mod error;
+pub mod parser;
+pub mod interpreter;
+pub mod fingerprint;
+
+mod runtime;
+mod llvm;
+mod c;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,394 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::action::Action;
+
+const STACK_SIZE: usize = 1048576;
+
+pub fn supports(action: &Action) -> bool {
+    match action {
+        Action::TablePut(..) | Action::Store(..) | Action::TableGet | Action::Interpret(..) | Action::Split(_) | Action::Vector(..)
+        | Action::BeginBlock(..) | Action::EndBlock(..) | Action::Under(..) | Action::SystemInfo(..) | Action::Execute
+        | Action::FileInput(..) | Action::FileOutput(..)
+        | Action::LoadFingerprint(..) | Action::UnloadFingerprint(..) | Action::Semantic(..) | Action::Call(_) => false,
+        Action::Iterate(action) => supports(action),
+        _ => true
+    }
+}
+
+pub fn write_first<W: Write>(writer: &mut W, used_actions: &HashSet<Action>) -> io::Result<()> {
+    writeln!(writer, "@stack = internal global [{} x i64] zeroinitializer", STACK_SIZE)?;
+    writeln!(writer, "@sp = internal global i64 0")?;
+
+    if used_actions.contains(&Action::Repeat(' ')) {
+        writeln!(writer, "@repeat = internal global i64 0")?;
+    }
+
+    if used_actions.contains(&Action::OutputNumber) {
+        writeln!(writer, "@number_format = private unnamed_addr constant [5 x i8] c\"%lld\\00\"")?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "declare void @abort()")?;
+
+    if used_actions.contains(&Action::OutputChar) {
+        writeln!(writer, "declare i32 @putchar(i32)")?;
+    }
+
+    if used_actions.contains(&Action::OutputNumber) {
+        writeln!(writer, "declare i32 @printf(ptr, ...)")?;
+    }
+
+    if used_actions.contains(&Action::InputChar) || used_actions.contains(&Action::InputNumber) {
+        writeln!(writer, "declare i32 @getchar()")?;
+    }
+
+    if used_actions.contains(&Action::Random(Vec::new())) {
+        writeln!(writer, "declare i32 @rand()")?;
+        writeln!(writer, "declare void @srand(i32)")?;
+        writeln!(writer, "declare i64 @time(ptr)")?;
+    }
+
+    writeln!(writer, "
+define internal void @push(i64 %v) {{
+entry:
+  %sp = load i64, ptr @sp
+  %full = icmp uge i64 %sp, {}
+  br i1 %full, label %overflow, label %store
+
+overflow:
+  call void @abort()
+  unreachable
+
+store:
+  %p = getelementptr [{} x i64], ptr @stack, i64 0, i64 %sp
+  store i64 %v, ptr %p
+  %next = add i64 %sp, 1
+  store i64 %next, ptr @sp
+  ret void
+}}
+
+define internal i64 @pop() {{
+entry:
+  %sp = load i64, ptr @sp
+  %empty = icmp eq i64 %sp, 0
+  br i1 %empty, label %underflow, label %load
+
+underflow:
+  ret i64 0
+
+load:
+  %next = sub i64 %sp, 1
+  store i64 %next, ptr @sp
+  %p = getelementptr [{} x i64], ptr @stack, i64 0, i64 %next
+  %v = load i64, ptr %p
+  ret i64 %v
+}}", STACK_SIZE, STACK_SIZE, STACK_SIZE)?;
+
+    for action in used_actions.iter() {
+        write_impl_to(writer, action)?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "define i32 @main() {{")?;
+    writeln!(writer, "entry:")?;
+
+    if used_actions.contains(&Action::Random(Vec::new())) {
+        writeln!(writer, "  %time = call i64 @time(ptr null)")?;
+        writeln!(writer, "  %seed = trunc i64 %time to i32")?;
+        writeln!(writer, "  call void @srand(i32 %seed)")?;
+    }
+
+    writeln!(writer, "  br label %state0")
+}
+
+pub fn write_state<W: Write>(writer: &mut W, state: usize, actions: &[Action]) -> io::Result<()> {
+    writeln!(writer, "\nstate{}:", state)?;
+
+    for (index, action) in actions.iter().enumerate() {
+        write_to(writer, &format!("%s{}.{}", state, index), action)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_end<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "}}")
+}
+
+// Temporaries and extra blocks are named after the given prefix so they are unique within @main
+fn write_to<W: Write>(writer: &mut W, prefix: &str, action: &Action) -> io::Result<()> {
+    match action {
+        Action::PushChar(c) => writeln!(writer, "  call void @push(i64 {})", *c as i64),
+        Action::PushNumber(n) => writeln!(writer, "  call void @push(i64 {})", n),
+        Action::OutputChar => writeln!(writer, "  call void @output_char()"),
+        Action::OutputNumber => writeln!(writer, "  call void @output_number()"),
+        Action::InputChar => writeln!(writer, "  call void @input_char()"),
+        Action::InputNumber => writeln!(writer, "  call void @input_number()"),
+        Action::Duplicate => writeln!(writer, "  call void @duplicate()"),
+        Action::Add => writeln!(writer, "  call void @add()"),
+        Action::Subtract => writeln!(writer, "  call void @subtract()"),
+        Action::Multiply => writeln!(writer, "  call void @multiply()"),
+        Action::Divide => writeln!(writer, "  call void @divide()"),
+        Action::Modulo => writeln!(writer, "  call void @modulo()"),
+        Action::Not => writeln!(writer, "  call void @not()"),
+        Action::Greater => writeln!(writer, "  call void @greater()"),
+        Action::Pop => writeln!(writer, "  call i64 @pop()"),
+        Action::Swap => writeln!(writer, "  call void @swap()"),
+        Action::Clear => writeln!(writer, "  store i64 0, ptr @sp"),
+
+        Action::Random(v) => {
+            writeln!(writer, "  {}.rand = call i32 @rand()", prefix)?;
+            writeln!(writer, "  {}.dir = urem i32 {}.rand, {}", prefix, prefix, v.len())?;
+            writeln!(writer, "  switch i32 {}.dir, label %state{} [", prefix, v[0])?;
+
+            for (i, target) in v.iter().enumerate().skip(1) {
+                writeln!(writer, "    i32 {}, label %state{}", i, target)?;
+            }
+
+            writeln!(writer, "  ]")
+        },
+
+        Action::Jump(v) => {
+            writeln!(writer, "  {}.n = call i64 @pop()", prefix)?;
+            writeln!(writer, "  {}.i = call i64 @modulus(i64 {}.n, i64 {})", prefix, prefix, v.len())?;
+            writeln!(writer, "  switch i64 {}.i, label %state{} [", prefix, v[0])?;
+
+            for (i, target) in v.iter().enumerate().skip(1) {
+                writeln!(writer, "    i64 {}, label %state{}", i, target)?;
+            }
+
+            writeln!(writer, "  ]")
+        },
+
+        Action::If(t, f) => {
+            writeln!(writer, "  {}.v = call i64 @pop()", prefix)?;
+            writeln!(writer, "  {}.c = icmp ne i64 {}.v, 0", prefix, prefix)?;
+            writeln!(writer, "  br i1 {}.c, label %state{}, label %state{}", prefix, t, f)
+        },
+
+        Action::Compare(s, l, r) => {
+            writeln!(writer, "  {}.a = call i64 @pop()", prefix)?;
+            writeln!(writer, "  {}.b = call i64 @pop()", prefix)?;
+            writeln!(writer, "  {}.lt = icmp slt i64 {}.a, {}.b", prefix, prefix, prefix)?;
+            writeln!(writer, "  br i1 {}.lt, label %state{}, label {}.ge", prefix, l, prefix)?;
+            writeln!(writer, "\n{}.ge:", &prefix[1..])?;
+            writeln!(writer, "  {}.gt = icmp sgt i64 {}.a, {}.b", prefix, prefix, prefix)?;
+            writeln!(writer, "  br i1 {}.gt, label %state{}, label %state{}", prefix, r, s)
+        },
+
+        Action::Iterate(action) => {
+            writeln!(writer, "  {}.n = call i64 @pop()", prefix)?;
+            writeln!(writer, "  br label {}.start", prefix)?;
+            writeln!(writer, "\n{}.start:", &prefix[1..])?;
+            writeln!(writer, "  br label {}.cond", prefix)?;
+            writeln!(writer, "\n{}.cond:", &prefix[1..])?;
+            writeln!(writer, "  {}.i = phi i64 [ {}.n, {}.start ], [ {}.next, {}.body ]", prefix, prefix, prefix, prefix, prefix)?;
+            writeln!(writer, "  {}.more = icmp sgt i64 {}.i, 0", prefix, prefix)?;
+            writeln!(writer, "  br i1 {}.more, label {}.body, label {}.done", prefix, prefix, prefix)?;
+            writeln!(writer, "\n{}.body:", &prefix[1..])?;
+            write_to(writer, &format!("{}.inner", prefix), action)?;
+            writeln!(writer, "  {}.next = sub i64 {}.i, 1", prefix, prefix)?;
+            writeln!(writer, "  br label {}.cond", prefix)?;
+            writeln!(writer, "\n{}.done:", &prefix[1..])
+        },
+
+        Action::Repeat(_) => {
+            writeln!(writer, "  {}.n = call i64 @pop()", prefix)?;
+            writeln!(writer, "  store i64 {}.n, ptr @repeat", prefix)
+        },
+
+        Action::Countdown(done) => {
+            writeln!(writer, "  {}.n = load i64, ptr @repeat", prefix)?;
+            writeln!(writer, "  {}.more = icmp sgt i64 {}.n, 0", prefix, prefix)?;
+            writeln!(writer, "  br i1 {}.more, label {}.next, label %state{}", prefix, prefix, done)?;
+            writeln!(writer, "\n{}.next:", &prefix[1..])?;
+            writeln!(writer, "  {}.left = sub i64 {}.n, 1", prefix, prefix)?;
+            writeln!(writer, "  store i64 {}.left, ptr @repeat", prefix)
+        },
+
+        Action::CallState(s) => writeln!(writer, "  br label %state{}", s),
+
+        Action::End => writeln!(writer, "  ret i32 0"),
+
+        Action::Quit => {
+            writeln!(writer, "  {}.v = call i64 @pop()", prefix)?;
+            writeln!(writer, "  {}.c = trunc i64 {}.v to i32", prefix, prefix)?;
+            writeln!(writer, "  ret i32 {}.c", prefix)
+        },
+
+        _ => Ok(())
+    }
+}
+
+fn write_impl_to<W: Write>(writer: &mut W, action: &Action) -> io::Result<()> {
+    match action {
+        Action::Duplicate => writeln!(writer, "
+define internal void @duplicate() {{
+entry:
+  %v = call i64 @pop()
+  call void @push(i64 %v)
+  call void @push(i64 %v)
+  ret void
+}}"),
+
+        Action::Add => write_binary(writer, "add", "add i64 %b, %a"),
+        Action::Subtract => write_binary(writer, "subtract", "sub i64 %b, %a"),
+        Action::Multiply => write_binary(writer, "multiply", "mul i64 %b, %a"),
+        Action::Divide => write_division(writer, "divide", "sdiv"),
+        Action::Modulo => write_division(writer, "modulo", "srem"),
+
+        Action::Not => writeln!(writer, "
+define internal void @not() {{
+entry:
+  %v = call i64 @pop()
+  %zero = icmp eq i64 %v, 0
+  %r = zext i1 %zero to i64
+  call void @push(i64 %r)
+  ret void
+}}"),
+
+        Action::Greater => writeln!(writer, "
+define internal void @greater() {{
+entry:
+  %a = call i64 @pop()
+  %b = call i64 @pop()
+  %gt = icmp sgt i64 %b, %a
+  %r = zext i1 %gt to i64
+  call void @push(i64 %r)
+  ret void
+}}"),
+
+        Action::Swap => writeln!(writer, "
+define internal void @swap() {{
+entry:
+  %a = call i64 @pop()
+  %b = call i64 @pop()
+  call void @push(i64 %a)
+  call void @push(i64 %b)
+  ret void
+}}"),
+
+        Action::OutputChar => writeln!(writer, "
+define internal void @output_char() {{
+entry:
+  %v = call i64 @pop()
+  %c = trunc i64 %v to i32
+  call i32 @putchar(i32 %c)
+  ret void
+}}"),
+
+        Action::OutputNumber => writeln!(writer, "
+define internal void @output_number() {{
+entry:
+  %v = call i64 @pop()
+  call i32 (ptr, ...) @printf(ptr @number_format, i64 %v)
+  ret void
+}}"),
+
+        Action::InputChar => writeln!(writer, "
+define internal void @input_char() {{
+entry:
+  %c = call i32 @getchar()
+  %v = sext i32 %c to i64
+  call void @push(i64 %v)
+  ret void
+}}"),
+
+        Action::InputNumber => writeln!(writer, "
+define internal void @input_number() {{
+entry:
+  br label %skip
+
+skip:
+  %negative = phi i1 [ false, %entry ], [ true, %minus ], [ false, %other ]
+  %c = call i32 @getchar()
+  %eof = icmp eq i32 %c, -1
+  br i1 %eof, label %none, label %check_minus
+
+check_minus:
+  %is_minus = icmp eq i32 %c, 45
+  br i1 %is_minus, label %minus, label %check_digit
+
+minus:
+  br label %skip
+
+check_digit:
+  %d = sub i32 %c, 48
+  %first = zext i32 %d to i64
+  %is_digit = icmp ult i32 %d, 10
+  br i1 %is_digit, label %digits, label %other
+
+other:
+  br label %skip
+
+digits:
+  %n = phi i64 [ %first, %check_digit ], [ %next, %more ]
+  %c2 = call i32 @getchar()
+  %d2 = sub i32 %c2, 48
+  %is_digit2 = icmp ult i32 %d2, 10
+  br i1 %is_digit2, label %more, label %done
+
+more:
+  %digit = zext i32 %d2 to i64
+  %shifted = mul i64 %n, 10
+  %next = add i64 %shifted, %digit
+  br label %digits
+
+done:
+  %negated = sub i64 0, %n
+  %result = select i1 %negative, i64 %negated, i64 %n
+  call void @push(i64 %result)
+  ret void
+
+none:
+  call void @push(i64 -1)
+  ret void
+}}"),
+
+        Action::Jump(_) => writeln!(writer, "
+define internal i64 @modulus(i64 %a, i64 %b) {{
+entry:
+  %r = srem i64 %a, %b
+  %negative = icmp slt i64 %r, 0
+  %wrapped = add i64 %r, %b
+  %m = select i1 %negative, i64 %wrapped, i64 %r
+  ret i64 %m
+}}"),
+
+        _ => Ok(())
+    }
+}
+
+fn write_binary<W: Write>(writer: &mut W, name: &str, op: &str) -> io::Result<()> {
+    writeln!(writer, "
+define internal void @{}() {{
+entry:
+  %a = call i64 @pop()
+  %b = call i64 @pop()
+  %r = {}
+  call void @push(i64 %r)
+  ret void
+}}", name, op)
+}
+
+// Division by zero pushes 0 rather than hitting undefined behaviour
+fn write_division<W: Write>(writer: &mut W, name: &str, op: &str) -> io::Result<()> {
+    writeln!(writer, "
+define internal void @{}() {{
+entry:
+  %a = call i64 @pop()
+  %b = call i64 @pop()
+  %zero = icmp eq i64 %a, 0
+  br i1 %zero, label %by_zero, label %apply
+
+by_zero:
+  call void @push(i64 0)
+  ret void
+
+apply:
+  %r = {} i64 %b, %a
+  call void @push(i64 %r)
+  ret void
+}}", name, op)
+}
@@ -1,755 +1,103 @@
-use std::os;
-use std::vec::Vec;
-use std::collections::{HashMap, HashSet};
-use std::collections::hash_map::Entry;
-use std::io::{BufferedReader, File, IoError};
-use std::io::stdio::{stdout, stderr};
-
-use ip::IP;
-use action::Action;
-use error::ParserError;
-
-mod ip;
-mod action;
-mod error;
-
-#[cfg(test)]
-mod test;
-
-struct Parser {
-    vars_enabled: bool,
-    exit_on_invalid: bool,
-    opt_eval: bool,
-    opt_j_eval: bool,
-    output_file: Option<String>
-}
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
 
-impl Parser {
-    fn new(vars: bool, inv: bool, eval: bool, jeval: bool, output: Option<String>) -> Parser {
-        Parser {
-            vars_enabled: vars,
-            exit_on_invalid: inv,
-            opt_eval: eval,
-            opt_j_eval: jeval,
-            output_file: output
-        }
-    }
-
-    fn parse(&self, filename: &String) -> Result<(), ParserError> {
-        self.read_file(filename)
-            .and_then(|a| self.parse_code(a))
-            .and_then(|a| self.write_output(a))
-    }
+use befunge::{Parser, Emit, Standard, Interpreter, ParserError};
+use befunge::fingerprint;
 
-    fn read_file(&self, filename: &String) -> Result<Vec<Vec<char>>, ParserError> {
-        let file = File::open(&Path::new(filename.as_slice()));
-
-        if file.is_ok() {
-            let mut reader = BufferedReader::new(file);
-
-            let mut grid = Vec::new();
-            grid.push(Vec::new());
-
-            let mut max_len = 0;
-
-            loop {
-                match reader.read_char() {
-                    Ok('\n') => {
-                        if grid.last().unwrap().len() > max_len {
-                            max_len = grid.last().unwrap().len()
-                        }
-                        grid.push(Vec::new())
-                    },
-
-                    Ok(c) => grid.last_mut().unwrap().push(c),
-                    Err(_) => break
-                }
-            }
-
-            let rows = grid.len() - 1;
-            grid.truncate(rows);
-
-            for row in grid.iter_mut() {
-                let inc = max_len - row.len();
-                if inc > 0 {
-                    row.grow(inc, ' ')
-                }
-            }
-
-            if grid.is_empty() {
-                Err(error::ParserError::FileEmptyError(filename.clone()))
-            } else {
-                Ok(grid)
-            }
-        } else {
-            Err(error::ParserError::FileReadError(filename.clone()))
-        }
+fn exit(err: ParserError) {
+    if writeln!(io::stderr(), "Error: {}", err).is_err() {
+        panic!("Error reporting error")
     }
+}
 
-    fn parse_code(&self, code: Vec<Vec<char>>) -> Result<(Vec<Vec<action::Action>>, HashSet<action::Action>), ParserError> {
-        let mut ip_queue = vec![IP::new(0, 0, 1, 0)];
-
-        let width = code[0].len();
-        let height = code.len();
-
-        let mut states = Vec::new();
-        states.grow_fn(height, |_| {
-            let mut v = Vec::new();
-            v.grow(width, HashMap::new());
-            v
-        });
-
-        let mut actions = Vec::new();
-        let mut used_actions = HashSet::new();
-
-        let mut stringmode = false;
-        let mut state = 0u;
-        let mut next_state = 1u;
-
-        loop {
-            if state >= ip_queue.len() {
-                break
-            }
-
-            let mut ip = ip_queue[state].clone();
-            if let Entry::Vacant(entry) = states[ip.y as uint][ip.x as uint].entry(ip.delta()) {
-            //if let Entry::Vacant(entry) = states[ip.y as uint][ip.x as uint].entry(ip.delta()) {
-                entry.set(state);
-            }
-
-            actions.push(Vec::new());
-
-            let mut first = true;
-            loop {
-                if stringmode {
-                    match code[ip.y as uint][ip.x as uint] {
-                        '"' => stringmode = false,
-                        c => { 
-                            actions[state].push(action::Action::PushChar(c));
-                            used_actions.insert(action::Action::PushChar(' '));
-                        }
-                    }
-                } else {
-                    match states[ip.y as uint][ip.x as uint].get(&ip.delta()) {
-                        Some(s) if !first => {
-                            actions[state].push(action::Action::CallState(*s));
-                            used_actions.insert(action::Action::CallState(0));
-                            break
-                        }
-
-                        _ => ()
-                    }
-
-                    first = false;
-
-                    match code[ip.y as uint][ip.x as uint] {
-                        '>' => ip.right(),
-                        '<' => ip.left(),
-                        '^' => ip.up(),
-                        'v' => ip.down(),
-                        '[' => ip.rotate_left(),
-                        ']' => ip.rotate_right(),
-                        'r' => ip.flip(),
-                        '#' => ip.advance(width, height),
-                        '"' => stringmode = true,
-
-                        c @ '0' ... '9' => { 
-                            actions[state].push(action::Action::PushNumber(c.to_digit(10).unwrap() as int));
-                            used_actions.insert(action::Action::PushNumber(0));
-                        },
-
-                        c @ 'a' ... 'f' => {
-                            actions[state].push(action::Action::PushNumber(c.to_digit(16).unwrap() as int));
-                            used_actions.insert(action::Action::PushNumber(0));
-                        },
-
-                        '~' => {
-                            actions[state].push(action::Action::InputChar);
-                            used_actions.insert(action::Action::InputChar);
-                        },
-
-                        ',' => {
-                            actions[state].push(action::Action::OutputChar);
-                            used_actions.insert(action::Action::OutputChar);
-                        },
-
-                        '.' => {
-                            actions[state].push(action::Action::OutputNumber);
-                            used_actions.insert(action::Action::OutputNumber);
-                        },
-
-                        '+' => {
-                            if self.opt_eval {
-                                match (actions[state].pop(), actions[state].pop()) {
-                                    (Some(action::Action::PushNumber(a)), Some(action::Action::PushNumber(b))) => actions[state].push(action::Action::PushNumber(a + b)),
-                                    (Some(action::Action::PushChar(c)), Some(action::Action::PushNumber(n))) 
-                                  | (Some(action::Action::PushNumber(n)), Some(action::Action::PushChar(c))) => actions[state].push(action::Action::PushNumber(n + (c as int))),
-                                    (Some(action::Action::PushChar(a)), Some(action::Action::PushChar(b))) => actions[state].push(action::Action::PushNumber(a as int + b as int)),
-
-                                    (Some(a), Some(b)) => {
-                                        actions[state].push(b);
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Add);
-                                        used_actions.insert(action::Action::Add);
-                                    },
-
-                                    (None, Some(a)) | (Some(a), None) => {
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Add);
-                                        used_actions.insert(action::Action::Add);
-                                    },
-
-                                    (None, None) => {
-                                        actions[state].push(action::Action::Add);
-                                        used_actions.insert(action::Action::Add);
-                                    },
-                                }
-                            } else {
-                                actions[state].push(action::Action::Add);
-                                used_actions.insert(action::Action::Add);
-                            }
-                        },
-
-                        '*' => {
-                            if self.opt_eval {
-                                match (actions[state].pop(), actions[state].pop()) {
-                                    (Some(action::Action::PushNumber(a)), Some(action::Action::PushNumber(b))) => actions[state].push(action::Action::PushNumber(a * b)),
-                                    (Some(action::Action::PushChar(c)), Some(action::Action::PushNumber(n))) 
-                                  | (Some(action::Action::PushNumber(n)), Some(action::Action::PushChar(c))) => actions[state].push(action::Action::PushNumber(n * (c as int))),
-                                    (Some(action::Action::PushChar(a)), Some(action::Action::PushChar(b))) => actions[state].push(action::Action::PushNumber(a as int * b as int)),
-
-                                    (Some(a), Some(b)) => {
-                                        actions[state].push(b);
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Multiply);
-                                        used_actions.insert(action::Action::Multiply);
-                                    },
-
-                                    (None, Some(a)) | (Some(a), None) => {
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Multiply);
-                                        used_actions.insert(action::Action::Multiply);
-                                    },
-
-                                    (None, None) => {
-                                        actions[state].push(action::Action::Multiply);
-                                        used_actions.insert(action::Action::Multiply);
-                                    },
-                                }
-                            } else {
-                                actions[state].push(action::Action::Multiply);
-                                used_actions.insert(action::Action::Multiply);
-                            }
-                        },
-
-                        '-' => {
-                            if self.opt_eval {
-                                match (actions[state].pop(), actions[state].pop()) {
-                                    (Some(action::Action::PushNumber(a)), Some(action::Action::PushNumber(b))) => actions[state].push(action::Action::PushNumber(b - a)),
-                                    (Some(action::Action::PushChar(a)), Some(action::Action::PushNumber(b))) => actions[state].push(action::Action::PushNumber(b - (a as int))),
-                                    (Some(action::Action::PushNumber(a)), Some(action::Action::PushChar(b))) => actions[state].push(action::Action::PushNumber((b as int) - a)),
-                                    (Some(action::Action::PushChar(a)), Some(action::Action::PushChar(b))) => actions[state].push(action::Action::PushNumber(b as int - a as int)),
-
-                                    (Some(a), Some(b)) => {
-                                        actions[state].push(b);
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Subtract);
-                                        used_actions.insert(action::Action::Subtract);
-                                    },
-
-                                    (None, Some(a)) | (Some(a), None) => {
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Subtract);
-                                        used_actions.insert(action::Action::Subtract);
-                                    },
-
-                                    (None, None) => {
-                                        actions[state].push(action::Action::Subtract);
-                                        used_actions.insert(action::Action::Subtract);
-                                    },
-                                }
-                            } else {
-                                actions[state].push(action::Action::Subtract);
-                                used_actions.insert(action::Action::Subtract);
-                            }
-                        },
-
-                        '/' => {
-                            if self.opt_eval {
-                                match (actions[state].pop(), actions[state].pop()) {
-                                    (Some(action::Action::PushNumber(a)), Some(action::Action::PushNumber(b))) => actions[state].push(action::Action::PushNumber(b / a)),
-                                    (Some(action::Action::PushChar(a)), Some(action::Action::PushNumber(b))) => actions[state].push(action::Action::PushNumber(b / (a as int))),
-                                    (Some(action::Action::PushNumber(a)), Some(action::Action::PushChar(b))) => actions[state].push(action::Action::PushNumber((b as int) / a)),
-                                    (Some(action::Action::PushChar(a)), Some(action::Action::PushChar(b))) => actions[state].push(action::Action::PushNumber(b as int / a as int)),
-
-                                    (Some(a), Some(b)) => {
-                                        actions[state].push(b);
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Divide);
-                                        used_actions.insert(action::Action::Divide);
-                                    },
-
-                                    (None, Some(a)) | (Some(a), None) => {
-                                        actions[state].push(a);
-                                        actions[state].push(action::Action::Divide);
-                                        used_actions.insert(action::Action::Divide);
-                                    },
-
-                                    (None, None) => {
-                                        actions[state].push(action::Action::Divide);
-                                        used_actions.insert(action::Action::Divide);
-                                    },
-                                }
-                            } else {
-                                actions[state].push(action::Action::Divide);
-                                used_actions.insert(action::Action::Divide);
-                            }
-                        },
-
-                        ':' => {
-                            actions[state].push(action::Action::Duplicate);
-                            used_actions.insert(action::Action::Duplicate);
-                        },
-
-                        '$' => {
-                            actions[state].push(action::Action::Pop);
-                            used_actions.insert(action::Action::Pop);
-                        },
-
-                        '\\' => {
-                            actions[state].push(action::Action::Swap);
-                            used_actions.insert(action::Action::Swap);
-                        },
-
-                        '\'' => {
-                            ip.advance(width, height);
-                            actions[state].push(action::Action::PushChar(code[ip.y as uint][ip.x as uint]));
-                            used_actions.insert(action::Action::PushChar(' '));
-                        },
-
-                        '?' => {
-                            let new_up = ip.new_up(width, height);
-                            let new_down = ip.new_down(width, height);
-                            let new_left = ip.new_left(width, height);
-                            let new_right = ip.new_right(width, height);
-
-                            let up_state = match states[new_up.y as uint][new_up.x as uint].entry(new_up.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(new_up);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            let down_state = match states[new_down.y as uint][new_down.x as uint].entry(new_down.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(new_down);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            let left_state = match states[new_left.y as uint][new_left.x as uint].entry(new_left.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(new_left);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            let right_state = match states[new_right.y as uint][new_right.x as uint].entry(new_right.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(new_right);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            actions[state].push(action::Action::Random(up_state, down_state, left_state, right_state));
-                            used_actions.insert(action::Action::Random(0, 0, 0, 0));
-                            break;
-                        }
-
-                        'j' => {
-                            match actions[state].pop() {
-                                Some(action::Action::PushNumber(n)) if self.opt_j_eval => {
-                                    let mut new_ip = ip.clone();
-                                    let mut r = range(0, n + 1);
-
-                                    if n < 0 {
-                                        new_ip.flip();
-                                        r = range(1, n)
-                                    }
-
-                                    for _ in r {
-                                        new_ip.advance(width, height);
-                                    }
-
-                                    let new_state = match states[new_ip.y as uint][new_ip.x as uint].entry(new_ip.delta()) {
-                                        Entry::Vacant(entry) => {
-                                            entry.set(next_state);
-                                            ip_queue.push(new_ip);
-                                            next_state += 1;
-
-                                            next_state - 1
-                                        },
-
-                                        Entry::Occupied(entry) => *entry.into_mut()
-                                    };
-
-                                    actions[state].push(action::Action::CallState(new_state));
-                                    used_actions.insert(action::Action::CallState(0));
-                                    break;
-                                },
-
-                                Some(action::Action::PushChar(c)) if self.opt_j_eval => {
-                                    let mut new_ip = ip.clone();
-                                    let n = c as int;
-                                    let mut r = range(0, n + 1);
-
-                                    if n < 0 {
-                                        new_ip.flip();
-                                        r = range(1, n)
-                                    }
-
-                                    for _ in r {
-                                        new_ip.advance(width, height);
-                                    }
-
-                                    let new_state = match states[new_ip.y as uint][new_ip.x as uint].entry(new_ip.delta()) {
-                                        Entry::Vacant(entry) => {
-                                            entry.set(next_state);
-                                            ip_queue.push(new_ip);
-                                            next_state += 1;
-
-                                            next_state - 1
-                                        },
-
-                                        Entry::Occupied(entry) => *entry.into_mut()
-                                    };
-
-                                    actions[state].push(action::Action::CallState(new_state));
-                                    used_actions.insert(action::Action::CallState(0));
-                                    break;
-                                },
-
-                                act => {
-                                    act.map(|a| actions[state].push(a));
-
-                                    let mut new_ip = ip.clone();
-                                    let mut jump_vec = Vec::new();
-
-                                    loop {
-                                        new_ip.advance(width, height);
-
-                                        let new_state = match states[new_ip.y as uint][new_ip.x as uint].entry(new_ip.delta()) {
-                                            Entry::Vacant(entry) => {
-                                                entry.set(next_state);
-                                                ip_queue.push(new_ip);
-                                                next_state += 1;
-
-                                                next_state - 1
-                                            },
-
-                                            Entry::Occupied(entry) => *entry.into_mut()
-                                        };
-                                        jump_vec.push(new_state);
-
-                                        if new_ip == ip {
-                                            break
-                                        }
-                                    };
-
-                                    actions[state].push(action::Action::Jump(jump_vec));
-                                    used_actions.insert(action::Action::Jump(Vec::new()));
-                                    break
-                                }
-                            }
-                        },
-
-                        c @ '_' | c @ '|' => {
-                            let true_ip = if c == '_' { ip.new_left(width, height) } else { ip.new_up(width, height) };
-                            let false_ip = if c == '_' { ip.new_right(width, height) } else { ip.new_down(width, height) };
-
-                            let true_state = match states[true_ip.y as uint][true_ip.x as uint].entry(true_ip.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(true_ip);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            let false_state = match states[false_ip.y as uint][false_ip.x as uint].entry(false_ip.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(false_ip);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            actions[state].push(action::Action::If(true_state, false_state));
-                            used_actions.insert(action::Action::If(0, 0));
-                            break
-                        },
-
-                        'w' => {
-                            let s_ip = ip.new_straight(width, height);
-                            let l_ip = ip.new_turn_left(width, height);
-                            let r_ip = ip.new_turn_right(width, height);
-
-                            let s_state = match states[s_ip.y as uint][s_ip.x as uint].entry(s_ip.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(s_ip);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            let l_state = match states[l_ip.y as uint][l_ip.x as uint].entry(l_ip.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(l_ip);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            let r_state = match states[r_ip.y as uint][r_ip.x as uint].entry(r_ip.delta()) {
-                                Entry::Vacant(entry) => {
-                                    entry.set(next_state);
-                                    ip_queue.push(r_ip);
-                                    next_state += 1;
-
-                                    next_state - 1
-                                },
-
-                                Entry::Occupied(entry) => *entry.into_mut()
-                            };
-
-                            actions[state].push(action::Action::Compare(s_state, l_state, r_state));
-                            used_actions.insert(action::Action::Compare(0, 0, 0));
-                            break
-                        },
-
-                        'n' => {
-                            actions[state].push(action::Action::Clear);
-                            used_actions.insert(action::Action::Clear);
-                        },
-
-                        '@' => {
-                            actions[state].push(action::Action::End);
-                            used_actions.insert(action::Action::End);
-                            break
-                        },
-
-                        'p' => {
-                            if self.vars_enabled {
-                                actions[state].push(action::Action::TablePut);
-                                used_actions.insert(action::Action::TablePut);
-                            } else {
-                                return Err(error::ParserError::VarsDisabled)
-                            }
-                        },
-
-                        'g' => {
-                            if self.vars_enabled {
-                                actions[state].push(action::Action::TableGet);
-                                used_actions.insert(action::Action::TableGet);
-                            } else {
-                                return Err(error::ParserError::VarsDisabled)
-                            }
-                        },
-
-                        ' ' => (),
-
-                        c @ _ => {
-                            if !self.exit_on_invalid {
-                                return Err(error::ParserError::UnexpectedChar(ip.x, ip.y, c))
-                            } else {
-                                ()
-                            }
-                        }
-                    }
-                }
-
-                ip.advance(width, height)
-            }
-
-            state += 1;
-        }
-
-        Ok((actions, used_actions))
+fn run(parser: &Parser, filename: &str) -> Result<(), ParserError> {
+    let code = parser.read_file(filename)?;
+    let mut interpreter = Interpreter::new(&code, parser.vars_enabled(), parser.exits_on_invalid(), io::stdin(), io::stdout())
+        .enable_execute(parser.execute_enabled())
+        .enable_file_io(parser.file_io_enabled())
+        .dimensions(parser.dimension_count())
+        .std(parser.standard())
+        .fingerprints(parser.fingerprints())
+        .handprint(parser.handprint_id());
+
+    interpreter.run()?;
+
+    match interpreter.exit_code() {
+        0 => Ok(()),
+        code => process::exit(code as i32)
     }
+}
 
-    fn write_first<W: Writer>(&self, writer: &mut W, used_actions: &HashSet<action::Action>) -> Result<(), IoError> {
-        writer.write_line("use std::char;")
-        .and_then(|_| writer.write_line("use std::vec::Vec;"))
-
-        .and_then(|_| if used_actions.contains(&action::Action::OutputChar) || used_actions.contains(&action::Action::OutputNumber) {
-            writer.write_line("use std::io::LineBufferedWriter;")
-            .and_then(|_| writer.write_line("use std::io::stdio::{StdWriter, stdout};"))
-        } else { Ok(()) })
-
-        .and_then(|_| if used_actions.contains(&action::Action::InputChar) || used_actions.contains(&action::Action::InputNumber) {
-            writer.write_line("use std::io::BufferedReader;")
-            .and_then(|_| writer.write_line("use std::io::stdio::{StdReader, stdin};"))
-        } else { Ok(()) })
-
-        .and_then(|_| if used_actions.contains(&action::Action::TableGet) || used_actions.contains(&action::Action::TablePut) {
-            writer.write_line("use std::collections::HashMap;")
-            .and_then(|_| writer.write_line("use std::collections::hash_map::Entry;"))
-        } else { Ok(()) })
-
-        .and_then(|_| if used_actions.contains(&action::Action::Random(0, 0, 0, 0)) {
-            writer.write_line("use std::rand::random;")
-        } else { Ok(()) })
-
-        .and_then(|_| writer.write_line(""))
-
-        .and_then(|_| if used_actions.contains(&action::Action::Jump(Vec::new())) {
-            writer.write_line("fn modulus(mut a: int, b: int) -> int {")
-            .and_then(|_| writer.write_line("    while a < 0 {"))
-            .and_then(|_| writer.write_line("        a += b"))
-            .and_then(|_| writer.write_line("    }"))
-            .and_then(|_| writer.write_line("    a % b"))
-            .and_then(|_| writer.write_line("}\n"))
-        } else { Ok(()) })
-
-        .and_then(|_| writer.write_line("struct Program {"))
-        .and_then(|_| writer.write_line("    stack: Vec<int>,"))
-
-        .and_then(|_| if used_actions.contains(&action::Action::OutputChar) || used_actions.contains(&action::Action::OutputNumber) {
-            writer.write_line("    output: LineBufferedWriter<StdWriter>,")
-        } else { Ok(()) })
-
-        .and_then(|_| if used_actions.contains(&action::Action::InputChar) || used_actions.contains(&action::Action::InputNumber) {
-            writer.write_line("    input: BufferedReader<StdReader>,")
-        } else { Ok(()) })
-
-        .and_then(|_| if used_actions.contains(&action::Action::TableGet) || used_actions.contains(&action::Action::TablePut) {
-            writer.write_line("    table: HashMap<(int, int), int>,")
-        } else { Ok(()) })
-
-        .and_then(|_| writer.write_line("}\n"))
+fn compile(parser: &Parser, filename: &str, output: Option<String>) -> Result<(), ParserError> {
+    parser.parse_file(filename).and_then(|graph| match output {
+        Some(f) => match File::create(&f) {
+            Ok(mut writer) => parser.write(&mut writer, &graph),
+            Err(_) => Err(ParserError::OutputFileError(f))
+        },
 
-        .and_then(|_| writer.write_line("impl Program {"))
-        .and_then(|_| writer.write_line("    fn run() {"))
-        .and_then(|_| writer.write_line("        let mut p = Program {"))
-        .and_then(|_| writer.write_line("            stack: Vec::new(),"))
+        None => parser.write(&mut io::stdout(), &graph)
+    })
+}
 
-        .and_then(|_| if used_actions.contains(&action::Action::OutputChar) || used_actions.contains(&action::Action::OutputNumber) {
-            writer.write_line("            output: stdout(),")
-        } else { Ok(()) })
+fn print_usage() {
+    println!("Usage: 
+    ./befunge [options] [input]
+    ./befunge run [options] [input]
 
-        .and_then(|_| if used_actions.contains(&action::Action::InputChar) || used_actions.contains(&action::Action::InputNumber) {
-            writer.write_line("            input: stdin(),")
-        } else { Ok(()) })
+Modes:
+    run
+        Interpret the input directly instead of generating code.
 
-        .and_then(|_| if used_actions.contains(&action::Action::TableGet) || used_actions.contains(&action::Action::TablePut) {
-            writer.write_line("            table: HashMap::new(),")
-        } else { Ok(()) })
+Options:
+    -h | --help
+        Print this message.
 
-        .and_then(|_| writer.write_line("        };"))
-        .and_then(|_| writer.write_line(""))
-        .and_then(|_| writer.write_line("        p.state0();"))
-        .and_then(|_| writer.write_line("    }"))
+    -o | --output [filename]
+        Output code to given file. If not given, outputs to stdout.
 
-        .and_then(|_| used_actions.iter().fold(Ok(()), |acc, act| acc.and_then(|_| act.write_impl_to(writer))))
-    }
+    --emit=[rust|llvm-ir|c]
+        Selects the language of the generated code. Defaults to rust.
+        LLVM IR and C output do not support 'p' and 'g'.
 
-    fn write_end<W: Writer>(&self, writer: &mut W) -> Result<(), IoError> {
-        writer.write_line("}
+    --dimensions [1|2|3]
+        Selects Unefunge (1), Befunge (2) or Trefunge (3). Unefunge
+        reads the input as a single line with newlines ignored and
+        rejects '^', 'v', '|', '[', ']' and 'w'. In Trefunge form feeds
+        in the input separate planes and 'h', 'l' and 'm' move between
+        them. Defaults to 2.
 
-fn main() {
-    Program::run()
-}")
-    }
+    --std=[93|98]
+        Selects the language standard. Befunge-93 runs on a fixed 80x25
+        grid, treats instructions added by Funge-98 as invalid characters
+        and reads 0 from outside the grid with 'g', where 'p' does
+        nothing. Defaults to 98.
 
-    fn write_output(&self, (actions, used_actions): (Vec<Vec<action::Action>>, HashSet<action::Action>)) -> Result<(), ParserError> {
-        match self.output_file {
-            Some(ref f) => {
-                let mut writer = File::create(&Path::new(f.clone()));
-                self.write_first(&mut writer, &used_actions)
+    --enable-vars
+        Enables using 'p' and 'g' to modify and read the program grid.
+        Disabled by default as writing to compiled code falls back
+        to an interpreter embedded in the output.
 
-                .and_then(|_| actions.iter().enumerate().fold(Ok(()), |acc, (state, vec)| acc.and_then(|_| {
-                    writer.write_line(format!("\n    fn state{}(&mut self) {{", state).as_slice())
-                    .and_then(|_| vec.iter().fold(Ok(()), |acc2, act| acc2.and_then(|_| act.write_to(&mut writer))))
-                    .and_then(|_| writer.write_line("    }"))
-                })))
+    --enable-execute
+        Lets '=' run its string as a shell command. Without it '='
+        pushes -1 and 'y' reports that it is unavailable.
 
-                .and_then(|_| self.write_end(&mut writer))
-                .map_err(|_| error::ParserError::OutputError)
-            },
-                
-            None => {
-                let mut writer = stdout(); 
-                self.write_first(&mut writer, &used_actions)
-
-                .and_then(|_| actions.iter().enumerate().fold(Ok(()), |acc, (state, vec)| acc.and_then(|_| {
-                    writer.write_line(format!("\n    fn state{}(&mut self) {{", state).as_slice())
-                    .and_then(|_| vec.iter().fold(Ok(()), |acc2, act| acc2.and_then(|_| act.write_to(&mut writer))))
-                    .and_then(|_| writer.write_line("    }"))
-                })))
-
-                .and_then(|_| self.write_end(&mut writer))
-                .map_err(|_| error::ParserError::OutputError)
-            }
-        }
-    }
-}
+    --enable-file-io
+        Lets 'i' and 'o' load files into and save them from the
+        program grid. Without it both reflect.
 
-fn exit(err: ParserError) {
-    let mut out = stderr();
-    if out.write_line(format!("Error: {}", err).as_slice()).is_err() {
-        panic!("Error reporting error")
-    }
-}
+    --fingerprints=[name,...]
+        Only lets '(' load the listed fingerprints, or none of them if
+        the list is empty. All of NULL, ROMA, MODU, BOOL and HRTI are
+        available by default.
 
-fn print_usage() {
-    println!("Usage: 
-    ./befunge [options] [input]
+    --no-fingerprint=[name]
+        Stops '(' from loading the given fingerprint.
 
-Options:
-    -h | --help 
-        Print this message.
-        
-    -o | --output [filename]
-        Output code to given file. If not given, outputs to stdout.
-        
-    --enable-vars
-        Enables using 'p' and 'g' to store and retrieve variables.
-        Disabled by default as this potentially allows invalid befunge.
+    --handprint=[name]
+        Sets the handprint 'y' reports, packed from the name
+        like a fingerprint id. Defaults to RBEF.
 
     --no-eval
         Disables evaluating constant expressions
@@ -757,41 +105,88 @@ Options:
 }
 
 fn main() {
-    let args = os::args();
+    let args: Vec<String> = env::args().collect();
 
     let mut help = false;
+    let mut run_mode = false;
     let mut vars = false;
     let mut inv = false;
     let mut eval = true;
     let mut jeval = true;
+    let mut execute = false;
+    let mut file_io = false;
+    let mut fingerprints: Vec<String> = fingerprint::registry().iter().map(|f| f.name().to_string()).collect();
+    let mut emit = Emit::Rust;
+    let mut handprint = fingerprint::id(fingerprint::HANDPRINT);
+    let mut dimensions = 2;
+    let mut std = Standard::Funge98;
     let mut filename = None;
     let mut output = None;
 
-    let mut i = 1u;
+    let mut i = 1;
     loop {
         if i >= args.len() {
             break
         }
 
-        match args[i].as_slice() {
+        match args[i].as_str() {
+            "run" if i == 1 => run_mode = true,
+
             "-h" | "--help" => {
                 help = true;
                 break
             },
 
-            "-o" | "--output" => {
+            "-o" | "--output" if i + 1 < args.len() => {
                 output = Some(args[i + 1].clone());
                 i += 1
             },
 
+            "--dimensions" if i + 1 < args.len() => {
+                dimensions = match args[i + 1].as_str() {
+                    "1" => 1,
+                    "2" => 2,
+                    "3" => 3,
+                    _ => return exit(ParserError::CmdError)
+                };
+                i += 1
+            },
+
             "-e" | "--exit-on-invalid" => inv = true,
 
             "-v" | "--enable-vars" => vars = true,
 
+            "--enable-execute" => execute = true,
+
+            "--enable-file-io" => file_io = true,
+
             "--no-eval" => eval = false,
 
             "--no-j-eval" => jeval = false,
 
+            "--emit=rust" => emit = Emit::Rust,
+            "--emit=llvm-ir" => emit = Emit::LlvmIr,
+            "--emit=c" => emit = Emit::C,
+
+            s if s.starts_with("--emit=") => return exit(ParserError::CmdError),
+
+            "--std=93" => std = Standard::Befunge93,
+            "--std=98" => std = Standard::Funge98,
+
+            s if s.starts_with("--std=") => return exit(ParserError::CmdError),
+
+            s if s.starts_with("--fingerprints=") => {
+                // An empty list disables every fingerprint
+                fingerprints = s["--fingerprints=".len()..].split(',').filter(|name| !name.is_empty()).map(|name| name.to_string()).collect()
+            },
+
+            s if s.starts_with("--no-fingerprint=") => {
+                let name = &s["--no-fingerprint=".len()..];
+                fingerprints.retain(|f| f != name)
+            },
+
+            s if s.starts_with("--handprint=") => handprint = fingerprint::id(&s["--handprint=".len()..]),
+
             s => filename = Some(s.to_string())
         }
 
@@ -802,10 +197,40 @@ fn main() {
         return print_usage()
     }
 
-    let parser = Parser::new(vars, inv, eval, jeval, output);
+    // Befunge-93 only has the one shape
+    if std == Standard::Befunge93 && dimensions != 2 {
+        return exit(ParserError::CmdError)
+    }
+
+    let registry = fingerprint::registry();
+    if fingerprints.iter().any(|name| !registry.iter().any(|f| f.name() == name)) {
+        return exit(ParserError::CmdError)
+    }
+
+    let mut parser = Parser::new()
+        .enable_vars(vars)
+        .exit_on_invalid(inv)
+        .enable_execute(execute)
+        .enable_file_io(file_io)
+        .eval(eval)
+        .j_eval(jeval)
+        .handprint(handprint)
+        .dimensions(dimensions)
+        .std(std)
+        .emit(emit);
+
+    for f in registry.iter() {
+        parser = parser.enable_fingerprint(f.name(), fingerprints.iter().any(|name| name == f.name()))
+    }
+
+    let filename = filename.unwrap();
+    let result = if run_mode {
+        run(&parser, &filename)
+    } else {
+        compile(&parser, &filename, output)
+    };
 
-    match parser.parse(&filename.unwrap()) {
-        Err(e) => exit(e),
-        _ => ()
+    if let Err(e) = result {
+        exit(e)
     }
 }
@@ -0,0 +1,1471 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::ip::IP;
+use crate::action::Action;
+use crate::error::ParserError;
+use crate::runtime;
+use crate::llvm;
+use crate::c;
+use crate::fingerprint;
+
+// Largest constant 'k' count that gets unrolled into the state, bigger ones loop at runtime
+const UNROLL_LIMIT: i64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Rust,
+    LlvmIr,
+    C
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Standard {
+    Befunge93,
+    Funge98
+}
+
+impl Standard {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Standard::Befunge93 => "Befunge-93",
+            Standard::Funge98 => "Funge-98"
+        }
+    }
+
+    // Whether the instruction exists in this standard
+    pub fn supports(&self, c: char) -> bool {
+        match self {
+            Standard::Befunge93 => "0123456789+-*/%!`><^v?_|\":\\$.,#gp&~@ ".contains(c),
+            Standard::Funge98 => true
+        }
+    }
+}
+
+pub struct StateGraph {
+    pub code: Vec<Vec<Vec<char>>>,
+    pub states: Vec<Vec<Action>>,
+    pub used_actions: HashSet<Action>,
+    pub compiled: Vec<Vec<Vec<bool>>>,
+    // Where each state starts, whether it starts in stringmode, and whether it repeats the instruction 'k' found
+    pub starts: Vec<(IP, bool, bool)>
+}
+
+#[derive(Clone)]
+pub struct Parser {
+    vars_enabled: bool,
+    exit_on_invalid: bool,
+    opt_eval: bool,
+    opt_j_eval: bool,
+    execute_enabled: bool,
+    file_io_enabled: bool,
+    fingerprints: Vec<i64>,
+    handprint: i64,
+    dimensions: usize,
+    std: Standard,
+    emit: Emit
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            vars_enabled: false,
+            exit_on_invalid: false,
+            opt_eval: true,
+            opt_j_eval: true,
+            execute_enabled: false,
+            file_io_enabled: false,
+            fingerprints: fingerprint::registry().iter().map(|fingerprint| fingerprint.id()).collect(),
+            handprint: fingerprint::id(fingerprint::HANDPRINT),
+            dimensions: 2,
+            std: Standard::Funge98,
+            emit: Emit::Rust
+        }
+    }
+
+    pub fn enable_vars(mut self, enabled: bool) -> Parser {
+        self.vars_enabled = enabled;
+        self
+    }
+
+    // Lets '=' run shell commands, otherwise it always reports failure
+    pub fn enable_execute(mut self, enabled: bool) -> Parser {
+        self.execute_enabled = enabled;
+        self
+    }
+
+    // Lets 'i' and 'o' read and write files, otherwise they always reflect
+    pub fn enable_file_io(mut self, enabled: bool) -> Parser {
+        self.file_io_enabled = enabled;
+        self
+    }
+
+    // Whether '(' can load the named fingerprint, all of them are enabled by default
+    pub fn enable_fingerprint(mut self, name: &str, enabled: bool) -> Parser {
+        let id = fingerprint::id(name);
+        self.fingerprints.retain(|&other| other != id);
+
+        if enabled {
+            self.fingerprints.push(id)
+        }
+
+        self
+    }
+
+    pub fn exit_on_invalid(mut self, enabled: bool) -> Parser {
+        self.exit_on_invalid = enabled;
+        self
+    }
+
+    pub fn eval(mut self, enabled: bool) -> Parser {
+        self.opt_eval = enabled;
+        self
+    }
+
+    pub fn j_eval(mut self, enabled: bool) -> Parser {
+        self.opt_j_eval = enabled;
+        self
+    }
+
+    // The id 'y' reports for this implementation
+    pub fn handprint(mut self, handprint: i64) -> Parser {
+        self.handprint = handprint;
+        self
+    }
+
+    // 1 for Unefunge where the source is a single line, 2 for Befunge,
+    // or 3 for Trefunge where form feeds in the source separate planes
+    pub fn dimensions(mut self, dimensions: usize) -> Parser {
+        self.dimensions = dimensions;
+        self
+    }
+
+    // Befunge-93 only accepts its own instructions on a fixed 80x25 grid
+    pub fn std(mut self, std: Standard) -> Parser {
+        self.std = std;
+        self
+    }
+
+    pub fn emit(mut self, emit: Emit) -> Parser {
+        self.emit = emit;
+        self
+    }
+
+    pub fn vars_enabled(&self) -> bool {
+        self.vars_enabled
+    }
+
+    pub fn exits_on_invalid(&self) -> bool {
+        self.exit_on_invalid
+    }
+
+    pub fn execute_enabled(&self) -> bool {
+        self.execute_enabled
+    }
+
+    pub fn file_io_enabled(&self) -> bool {
+        self.file_io_enabled
+    }
+
+    pub fn fingerprints(&self) -> &[i64] {
+        &self.fingerprints
+    }
+
+    pub fn handprint_id(&self) -> i64 {
+        self.handprint
+    }
+
+    pub fn dimension_count(&self) -> usize {
+        self.dimensions
+    }
+
+    pub fn standard(&self) -> Standard {
+        self.std
+    }
+
+    pub fn compile(&self, source: &str) -> Result<String, ParserError> {
+        let mut writer = Vec::new();
+
+        self.parse(source)
+            .and_then(|graph| self.write(&mut writer, &graph))
+            .and_then(|_| String::from_utf8(writer).map_err(|_| ParserError::OutputError))
+    }
+
+    pub fn parse(&self, source: &str) -> Result<StateGraph, ParserError> {
+        self.read_source(source)
+            .and_then(|code| self.parse_code(code))
+    }
+
+    pub fn parse_file(&self, filename: &str) -> Result<StateGraph, ParserError> {
+        self.read_file(filename)
+            .and_then(|code| self.parse_code(code))
+    }
+
+    pub fn read_file(&self, filename: &str) -> Result<Vec<Vec<Vec<char>>>, ParserError> {
+        match fs::read_to_string(filename) {
+            Ok(source) => self.read_source(&source).map_err(|_| ParserError::FileEmptyError(filename.to_string())),
+            Err(_) => Err(ParserError::FileReadError(filename.to_string()))
+        }
+    }
+
+    pub fn read_source(&self, source: &str) -> Result<Vec<Vec<Vec<char>>>, ParserError> {
+        if self.dimensions == 1 {
+            let line: String = source.chars().filter(|&c| c != '\n' && c != '\r').collect();
+            let line: Vec<char> = line.trim_end_matches(' ').chars().collect();
+
+            return if line.is_empty() {
+                Err(ParserError::EmptySource)
+            } else {
+                Ok(vec![vec![line]])
+            }
+        }
+
+        let planes: Vec<&str> = if self.dimensions > 2 {
+            source.split('\x0c').collect()
+        } else {
+            vec![source]
+        };
+
+        // The program area is the bounding box of the source, which trailing spaces, blank lines and empty planes don't extend
+        let mut space: Vec<Vec<Vec<char>>> = planes.iter().map(|plane| {
+            let mut grid: Vec<Vec<char>> = plane.lines().map(|line| line.trim_end_matches(' ').chars().collect()).collect();
+            while grid.last().is_some_and(|row| row.is_empty()) {
+                grid.pop();
+            }
+
+            grid
+        }).collect();
+
+        while space.last().is_some_and(|grid| grid.is_empty()) {
+            space.pop();
+        }
+
+        let width = space.iter().flatten().map(|row| row.len()).max().unwrap_or(0);
+        let height = space.iter().map(|grid| grid.len()).max().unwrap_or(0);
+
+        if width == 0 {
+            return Err(ParserError::EmptySource)
+        }
+
+        // Befunge-93 programs always get the whole torus, and can't be any larger
+        let (width, height) = match self.std {
+            Standard::Befunge93 if width > 80 || height > 25 => return Err(ParserError::SourceTooLarge(width, height)),
+            Standard::Befunge93 => (80, 25),
+            Standard::Funge98 => (width, height)
+        };
+
+        for grid in space.iter_mut() {
+            grid.resize(height, Vec::new());
+
+            for row in grid.iter_mut() {
+                row.resize(width, ' ')
+            }
+        }
+
+        Ok(space)
+    }
+
+    pub fn parse_code(&self, code: Vec<Vec<Vec<char>>>) -> Result<StateGraph, ParserError> {
+        let graph = self.build_states(code, false)?;
+
+        // Split IPs take turns one instruction at a time, so once a 't' is reached every state only runs for a single tick
+        if graph.used_actions.contains(&Action::Split(0)) {
+            return self.build_states(graph.code, true)
+        }
+
+        Ok(graph)
+    }
+
+    fn build_states(&self, code: Vec<Vec<Vec<char>>>, concurrent: bool) -> Result<StateGraph, ParserError> {
+        let mut ip_queue = vec![Start { ip: IP::new(0, 0, 1, 0), stringmode: false, repeat: None }];
+
+        let width = code[0][0].len();
+        let height = code[0].len();
+        let depth = code.len();
+
+        let mut states: Vec<Vec<Vec<StateMap>>> = vec![vec![vec![HashMap::new(); width]; height]; depth];
+        let mut compiled = vec![vec![vec![false; width]; height]; depth];
+
+        let mut actions = Vec::new();
+        let mut used_actions = HashSet::new();
+
+        let mut state = 0;
+
+        while state < ip_queue.len() {
+            let Start { mut ip, mut stringmode, repeat } = ip_queue[state];
+            states[ip.z as usize][ip.y as usize][ip.x as usize].entry((ip.delta(), stringmode, repeat)).or_insert(state);
+
+            // Spaces and comments take no time, so every tick runs an actual instruction
+            if concurrent && !stringmode && repeat.is_none() {
+                skip_free(&code, &mut compiled, &mut ip, (width, height, depth));
+            }
+
+            // Where the IP carries on once an instruction has turned it, staying in place while 'k' is still repeating
+            // the instruction. Only instructions outside stringmode branch, so the next state never starts in it.
+            let follow = |mut ip: IP, turn: fn(&mut IP)| {
+                turn(&mut ip);
+
+                if repeat.is_none() {
+                    ip.advance(width, height, depth)
+                }
+
+                Start { ip, stringmode: false, repeat }
+            };
+
+            actions.push(Vec::new());
+
+            let mut first = true;
+            let mut iterate: Option<(char, i64)> = None;
+
+            // Each repetition of a runtime count is its own state, which moves on once the count runs out
+            if let Some(c) = repeat {
+                let done = state_for(&mut states, &mut ip_queue, Start { ip: ip.new_straight(width, height, depth), stringmode, repeat: None });
+                actions[state].push(Action::Countdown(done));
+                used_actions.insert(Action::Countdown(0));
+                iterate = Some((c, 0));
+            }
+
+            loop {
+                // While iterating, the instruction 'k' found is re-run in place of the cell under the IP
+                let current = match iterate {
+                    Some((c, _)) => c,
+                    None => code[ip.z as usize][ip.y as usize][ip.x as usize]
+                };
+
+                if stringmode {
+                    compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+
+                    match current {
+                        '"' => stringmode = false,
+                        c => {
+                            actions[state].push(Action::PushChar(c));
+                            used_actions.insert(Action::PushChar(' '));
+                        }
+                    }
+                } else {
+                    if !first && iterate.is_none() {
+                        if let Some(s) = states[ip.z as usize][ip.y as usize][ip.x as usize].get(&(ip.delta(), false, None)) {
+                            actions[state].push(Action::CallState(*s));
+                            used_actions.insert(Action::CallState(0));
+                            break
+                        }
+                    }
+
+                    first = false;
+                    compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+
+                    match current {
+                        // Instructions the standard doesn't have are invalid characters, so they're only ignored with exit_on_invalid
+                        c if !self.std.supports(c) => if !self.exit_on_invalid {
+                            return Err(ParserError::UnsupportedInstruction(c, self.std.name().to_string()))
+                        },
+
+                        c @ ('^' | 'v' | '|' | '[' | ']' | 'w') if self.dimensions == 1 => if !self.exit_on_invalid {
+                            return Err(ParserError::UnsupportedInstruction(c, "Unefunge-98".to_string()))
+                        },
+
+                        '>' => ip.right(),
+                        '<' => ip.left(),
+                        '^' => ip.up(),
+                        'v' => ip.down(),
+                        '[' => ip.rotate_left(),
+                        ']' => ip.rotate_right(),
+                        'r' => ip.flip(),
+                        'h' if self.dimensions > 2 => ip.high(),
+                        'l' if self.dimensions > 2 => ip.low(),
+                        '#' => ip.advance(width, height, depth),
+                        '"' => stringmode = true,
+
+                        ';' => loop {
+                            ip.advance(width, height, depth);
+                            compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+
+                            if code[ip.z as usize][ip.y as usize][ip.x as usize] == ';' {
+                                break
+                            }
+                        },
+
+                        c @ '0'..='9' => {
+                            actions[state].push(Action::PushNumber(c.to_digit(10).unwrap() as i64));
+                            used_actions.insert(Action::PushNumber(0));
+                        },
+
+                        c @ 'a'..='f' => {
+                            actions[state].push(Action::PushNumber(c.to_digit(16).unwrap() as i64));
+                            used_actions.insert(Action::PushNumber(0));
+                        },
+
+                        '~' => {
+                            actions[state].push(Action::InputChar);
+                            used_actions.insert(Action::InputChar);
+                        },
+
+                        '&' => {
+                            actions[state].push(Action::InputNumber);
+                            used_actions.insert(Action::InputNumber);
+                        },
+
+                        ',' => {
+                            actions[state].push(Action::OutputChar);
+                            used_actions.insert(Action::OutputChar);
+                        },
+
+                        '.' => {
+                            actions[state].push(Action::OutputNumber);
+                            used_actions.insert(Action::OutputNumber);
+                        },
+
+                        '+' => {
+                            if self.opt_eval {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(a.wrapping_add(b))),
+                                    (Some(Action::PushChar(c)), Some(Action::PushNumber(n)))
+                                  | (Some(Action::PushNumber(n)), Some(Action::PushChar(c))) => actions[state].push(Action::PushNumber(n.wrapping_add(c as i64))),
+                                    (Some(Action::PushChar(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(a as i64 + b as i64)),
+
+                                    (Some(a), Some(b)) => {
+                                        actions[state].push(b);
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Add);
+                                        used_actions.insert(Action::Add);
+                                    },
+
+                                    (None, Some(a)) | (Some(a), None) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Add);
+                                        used_actions.insert(Action::Add);
+                                    },
+
+                                    (None, None) => {
+                                        actions[state].push(Action::Add);
+                                        used_actions.insert(Action::Add);
+                                    },
+                                }
+                            } else {
+                                actions[state].push(Action::Add);
+                                used_actions.insert(Action::Add);
+                            }
+                        },
+
+                        '*' => {
+                            if self.opt_eval {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(a.wrapping_mul(b))),
+                                    (Some(Action::PushChar(c)), Some(Action::PushNumber(n)))
+                                  | (Some(Action::PushNumber(n)), Some(Action::PushChar(c))) => actions[state].push(Action::PushNumber(n.wrapping_mul(c as i64))),
+                                    (Some(Action::PushChar(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber((a as i64).wrapping_mul(b as i64))),
+
+                                    (Some(a), Some(b)) => {
+                                        actions[state].push(b);
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Multiply);
+                                        used_actions.insert(Action::Multiply);
+                                    },
+
+                                    (None, Some(a)) | (Some(a), None) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Multiply);
+                                        used_actions.insert(Action::Multiply);
+                                    },
+
+                                    (None, None) => {
+                                        actions[state].push(Action::Multiply);
+                                        used_actions.insert(Action::Multiply);
+                                    },
+                                }
+                            } else {
+                                actions[state].push(Action::Multiply);
+                                used_actions.insert(Action::Multiply);
+                            }
+                        },
+
+                        '-' => {
+                            if self.opt_eval {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(b.wrapping_sub(a))),
+                                    (Some(Action::PushChar(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(b.wrapping_sub(a as i64))),
+                                    (Some(Action::PushNumber(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber((b as i64).wrapping_sub(a))),
+                                    (Some(Action::PushChar(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(b as i64 - a as i64)),
+
+                                    (Some(a), Some(b)) => {
+                                        actions[state].push(b);
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Subtract);
+                                        used_actions.insert(Action::Subtract);
+                                    },
+
+                                    (None, Some(a)) | (Some(a), None) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Subtract);
+                                        used_actions.insert(Action::Subtract);
+                                    },
+
+                                    (None, None) => {
+                                        actions[state].push(Action::Subtract);
+                                        used_actions.insert(Action::Subtract);
+                                    },
+                                }
+                            } else {
+                                actions[state].push(Action::Subtract);
+                                used_actions.insert(Action::Subtract);
+                            }
+                        },
+
+                        '/' => {
+                            if self.opt_eval {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(if a == 0 { 0 } else { b.wrapping_div(a) })),
+                                    (Some(Action::PushChar(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(if a as i64 == 0 { 0 } else { b.wrapping_div(a as i64) })),
+                                    (Some(Action::PushNumber(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(if a == 0 { 0 } else { (b as i64).wrapping_div(a) })),
+                                    (Some(Action::PushChar(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(if a as i64 == 0 { 0 } else { b as i64 / a as i64 })),
+
+                                    (Some(a), Some(b)) => {
+                                        actions[state].push(b);
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Divide);
+                                        used_actions.insert(Action::Divide);
+                                    },
+
+                                    (None, Some(a)) | (Some(a), None) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Divide);
+                                        used_actions.insert(Action::Divide);
+                                    },
+
+                                    (None, None) => {
+                                        actions[state].push(Action::Divide);
+                                        used_actions.insert(Action::Divide);
+                                    },
+                                }
+                            } else {
+                                actions[state].push(Action::Divide);
+                                used_actions.insert(Action::Divide);
+                            }
+                        },
+
+                        '%' => {
+                            if self.opt_eval {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(if a == 0 { 0 } else { b.wrapping_rem(a) })),
+                                    (Some(Action::PushChar(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(if a as i64 == 0 { 0 } else { b.wrapping_rem(a as i64) })),
+                                    (Some(Action::PushNumber(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(if a == 0 { 0 } else { (b as i64).wrapping_rem(a) })),
+                                    (Some(Action::PushChar(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(if a as i64 == 0 { 0 } else { b as i64 % a as i64 })),
+
+                                    (Some(a), Some(b)) => {
+                                        actions[state].push(b);
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Modulo);
+                                        used_actions.insert(Action::Modulo);
+                                    },
+
+                                    (None, Some(a)) | (Some(a), None) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Modulo);
+                                        used_actions.insert(Action::Modulo);
+                                    },
+
+                                    (None, None) => {
+                                        actions[state].push(Action::Modulo);
+                                        used_actions.insert(Action::Modulo);
+                                    },
+                                }
+                            } else {
+                                actions[state].push(Action::Modulo);
+                                used_actions.insert(Action::Modulo);
+                            }
+                        },
+
+                        '!' => {
+                            if self.opt_eval {
+                                match actions[state].pop() {
+                                    Some(Action::PushNumber(n)) => actions[state].push(Action::PushNumber(if n == 0 { 1 } else { 0 })),
+                                    Some(Action::PushChar(c)) => actions[state].push(Action::PushNumber(if c as i64 == 0 { 1 } else { 0 })),
+
+                                    Some(a) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Not);
+                                        used_actions.insert(Action::Not);
+                                    },
+
+                                    None => {
+                                        actions[state].push(Action::Not);
+                                        used_actions.insert(Action::Not);
+                                    }
+                                }
+                            } else {
+                                actions[state].push(Action::Not);
+                                used_actions.insert(Action::Not);
+                            }
+                        },
+
+                        '`' => {
+                            if self.opt_eval {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(if b > a { 1 } else { 0 })),
+                                    (Some(Action::PushChar(a)), Some(Action::PushNumber(b))) => actions[state].push(Action::PushNumber(if b > a as i64 { 1 } else { 0 })),
+                                    (Some(Action::PushNumber(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(if b as i64 > a { 1 } else { 0 })),
+                                    (Some(Action::PushChar(a)), Some(Action::PushChar(b))) => actions[state].push(Action::PushNumber(if b > a { 1 } else { 0 })),
+
+                                    (Some(a), Some(b)) => {
+                                        actions[state].push(b);
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Greater);
+                                        used_actions.insert(Action::Greater);
+                                    },
+
+                                    (None, Some(a)) | (Some(a), None) => {
+                                        actions[state].push(a);
+                                        actions[state].push(Action::Greater);
+                                        used_actions.insert(Action::Greater);
+                                    },
+
+                                    (None, None) => {
+                                        actions[state].push(Action::Greater);
+                                        used_actions.insert(Action::Greater);
+                                    },
+                                }
+                            } else {
+                                actions[state].push(Action::Greater);
+                                used_actions.insert(Action::Greater);
+                            }
+                        },
+
+                        ':' => {
+                            actions[state].push(Action::Duplicate);
+                            used_actions.insert(Action::Duplicate);
+                        },
+
+                        '$' => {
+                            actions[state].push(Action::Pop);
+                            used_actions.insert(Action::Pop);
+                        },
+
+                        '\\' => {
+                            actions[state].push(Action::Swap);
+                            used_actions.insert(Action::Swap);
+                        },
+
+                        '\'' => {
+                            ip.advance(width, height, depth);
+                            compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+                            actions[state].push(Action::PushChar(code[ip.z as usize][ip.y as usize][ip.x as usize]));
+                            used_actions.insert(Action::PushChar(' '));
+                        },
+
+                        // One state for each direction there is in this many dimensions
+                        '?' => {
+                            let turns: &[fn(&mut IP)] = match self.dimensions {
+                                1 => &[IP::left, IP::right],
+                                2 => &[IP::up, IP::down, IP::left, IP::right],
+                                _ => &[IP::up, IP::down, IP::left, IP::right, IP::high, IP::low]
+                            };
+
+                            let targets = turns.iter().map(|&turn| state_for(&mut states, &mut ip_queue, follow(ip, turn))).collect();
+
+                            actions[state].push(Action::Random(targets));
+                            used_actions.insert(Action::Random(Vec::new()));
+                            break;
+                        }
+
+                        'j' => {
+                            let count = match actions[state].pop() {
+                                Some(Action::PushNumber(n)) if self.opt_j_eval => Some(n),
+                                Some(Action::PushChar(c)) if self.opt_j_eval => Some(c as i64),
+
+                                act => {
+                                    if let Some(a) = act {
+                                        actions[state].push(a)
+                                    }
+
+                                    None
+                                }
+                            };
+
+                            match count {
+                                Some(n) => {
+                                    let mut new_ip = ip;
+                                    new_ip.jump(n, width, height, depth);
+
+                                    let new_state = state_for(&mut states, &mut ip_queue, follow(new_ip, |_| ()));
+                                    actions[state].push(Action::CallState(new_state));
+                                    used_actions.insert(Action::CallState(0));
+                                    break;
+                                },
+
+                                None => {
+                                    // One state for each cell the jump can land on, indexed by the count modulo the loop length
+                                    let mut new_ip = ip;
+                                    let mut jump_vec = Vec::new();
+
+                                    loop {
+                                        jump_vec.push(state_for(&mut states, &mut ip_queue, follow(new_ip, |_| ())));
+                                        new_ip.advance(width, height, depth);
+
+                                        if new_ip == ip {
+                                            break
+                                        }
+                                    };
+
+                                    actions[state].push(Action::Jump(jump_vec));
+                                    used_actions.insert(Action::Jump(Vec::new()));
+                                    break
+                                }
+                            }
+                        },
+
+                        c @ '_' | c @ '|' | c @ 'm' if c != 'm' || self.dimensions > 2 => {
+                            let (true_ip, false_ip) = match c {
+                                '_' => (follow(ip, IP::left), follow(ip, IP::right)),
+                                '|' => (follow(ip, IP::up), follow(ip, IP::down)),
+                                _ => (follow(ip, IP::high), follow(ip, IP::low))
+                            };
+
+                            let true_state = state_for(&mut states, &mut ip_queue, true_ip);
+                            let false_state = state_for(&mut states, &mut ip_queue, false_ip);
+
+                            actions[state].push(Action::If(true_state, false_state));
+                            used_actions.insert(Action::If(0, 0));
+                            break
+                        },
+
+                        'w' => {
+                            let s_state = state_for(&mut states, &mut ip_queue, follow(ip, |_| ()));
+                            let l_state = state_for(&mut states, &mut ip_queue, follow(ip, IP::rotate_left));
+                            let r_state = state_for(&mut states, &mut ip_queue, follow(ip, IP::rotate_right));
+
+                            actions[state].push(Action::Compare(s_state, l_state, r_state));
+                            used_actions.insert(Action::Compare(0, 0, 0));
+                            break
+                        },
+
+                        'n' => {
+                            actions[state].push(Action::Clear);
+                            used_actions.insert(Action::Clear);
+                        },
+
+                        '@' => {
+                            actions[state].push(Action::End);
+                            used_actions.insert(Action::End);
+                            break
+                        },
+
+                        '=' => {
+                            actions[state].push(Action::Execute);
+                            used_actions.insert(Action::Execute);
+                        },
+
+                        'q' => {
+                            actions[state].push(Action::Quit);
+                            used_actions.insert(Action::Quit);
+                            break
+                        },
+
+                        'k' => {
+                            let k_ip = ip;
+
+                            loop {
+                                ip.advance(width, height, depth);
+                                compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+
+                                match code[ip.z as usize][ip.y as usize][ip.x as usize] {
+                                    ' ' => (),
+
+                                    ';' => loop {
+                                        ip.advance(width, height, depth);
+                                        compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+
+                                        if code[ip.z as usize][ip.y as usize][ip.x as usize] == ';' {
+                                            break
+                                        }
+                                    },
+
+                                    _ => break
+                                }
+                            }
+
+                            let target = code[ip.z as usize][ip.y as usize][ip.x as usize];
+                            let count = if self.opt_j_eval {
+                                match actions[state].pop() {
+                                    Some(Action::PushNumber(n)) => Some(n),
+                                    Some(Action::PushChar(c)) => Some(c as i64),
+
+                                    act => {
+                                        if let Some(a) = act {
+                                            actions[state].push(a)
+                                        }
+
+                                        None
+                                    }
+                                }
+                            } else {
+                                None
+                            };
+
+                            match (count, simple_action(target)) {
+                                (Some(n), _) if n <= 0 => (),
+
+                                // Instructions that end the state can't be unrolled in place, so they count down at runtime instead
+                                (Some(n), _) if !concurrent && (n <= UNROLL_LIMIT || "?@".contains(target)) && (n == 1 || !("wjkpstio}u()xm".contains(target) || target.is_ascii_uppercase())) => {
+                                    let runs = match target {
+                                        // Only the last branch taken matters, the earlier ones just consume their value
+                                        '_' | '|' if target == '_' || self.dimensions > 1 => {
+                                            for _ in 1..n {
+                                                actions[state].push(Action::Pop);
+                                                used_actions.insert(Action::Pop);
+                                            }
+
+                                            1
+                                        },
+
+                                        '?' | '@' => 1,
+                                        _ => n
+                                    };
+
+                                    iterate = Some((target, runs))
+                                },
+
+                                // Writing to the grid can invalidate the repeated states, so the embedded interpreter runs those
+                                _ if "psi".contains(target) => {
+                                    if let Some(n) = count {
+                                        actions[state].push(Action::PushNumber(n));
+                                        used_actions.insert(Action::PushNumber(0));
+                                    }
+
+                                    actions[state].push(Action::Interpret(k_ip.x, k_ip.y, k_ip.z, k_ip.dx, k_ip.dy, k_ip.dz));
+                                    used_actions.extend(runtime::required_actions());
+                                    break
+                                },
+
+                                (_, Some(action)) if !concurrent => {
+                                    if let Some(n) = count {
+                                        actions[state].push(Action::PushNumber(n));
+                                        used_actions.insert(Action::PushNumber(0));
+                                    }
+
+                                    used_actions.insert(match action {
+                                        Action::PushNumber(_) => Action::PushNumber(0),
+                                        ref a => a.clone()
+                                    });
+                                    actions[state].push(Action::Iterate(Box::new(action)));
+                                },
+
+                                _ => {
+                                    if let Some(n) = count {
+                                        actions[state].push(Action::PushNumber(n));
+                                        used_actions.insert(Action::PushNumber(0));
+                                    }
+
+                                    let next = state_for(&mut states, &mut ip_queue, Start { ip, stringmode, repeat: Some(target) });
+                                    actions[state].push(Action::Repeat(target));
+                                    actions[state].push(Action::CallState(next));
+                                    used_actions.insert(Action::Repeat(' '));
+                                    used_actions.insert(Action::CallState(0));
+                                    break
+                                }
+                            }
+                        },
+
+                        'x' => {
+                            // Only Befunge deltas are folded, the other dimensions pop a different number of components
+                            let delta = if self.opt_eval && self.dimensions == 2 {
+                                match (actions[state].pop(), actions[state].pop()) {
+                                    (Some(Action::PushNumber(dy)), Some(Action::PushNumber(dx))) => Some((dx, dy)),
+                                    (Some(Action::PushChar(dy)), Some(Action::PushNumber(dx))) => Some((dx, dy as i64)),
+                                    (Some(Action::PushNumber(dy)), Some(Action::PushChar(dx))) => Some((dx as i64, dy)),
+                                    (Some(Action::PushChar(dy)), Some(Action::PushChar(dx))) => Some((dx as i64, dy as i64)),
+
+                                    (a, b) => {
+                                        if let Some(b) = b {
+                                            actions[state].push(b)
+                                        }
+
+                                        if let Some(a) = a {
+                                            actions[state].push(a)
+                                        }
+
+                                        None
+                                    }
+                                }
+                            } else {
+                                None
+                            };
+
+                            match delta {
+                                Some((dx, dy)) => {
+                                    ip.dx = dx;
+                                    ip.dy = dy;
+                                },
+
+                                None => {
+                                    // Unit deltas get compiled states, anything else is left to the embedded interpreter
+                                    let mut targets = Vec::new();
+
+                                    let mut deltas = Vec::new();
+
+                                    for dz in -1..=1 {
+                                        for dy in -1..=1 {
+                                            for dx in -1..=1 {
+                                                if (dx, dy, dz) != (0, 0, 0) && (dy == 0 || self.dimensions > 1) && (dz == 0 || self.dimensions > 2) {
+                                                    deltas.push((dx, dy, dz))
+                                                }
+                                            }
+                                        }
+                                    }
+
+                                    for (dx, dy, dz) in deltas {
+                                        let new_state = state_for(&mut states, &mut ip_queue, follow(IP { dx, dy, dz, ..ip }, |_| ()));
+
+                                        targets.push(((dx, dy, dz), new_state));
+                                    }
+
+                                    actions[state].push(Action::Vector(targets, ip.x, ip.y, ip.z, ip.dx, ip.dy, ip.dz));
+                                    used_actions.insert(Action::Vector(Vec::new(), 0, 0, 0, 0, 0, 0));
+                                    used_actions.extend(runtime::required_actions());
+                                    break
+                                }
+                            }
+                        },
+
+                        '{' => {
+                            let next = ip.new_straight(width, height, depth);
+                            actions[state].push(Action::BeginBlock(next.x, next.y, next.z));
+                            used_actions.insert(Action::BeginBlock(0, 0, 0));
+                        },
+
+                        c @ ('}' | 'u' | 'i' | 'o' | '(' | ')' | 'A'..='Z') => {
+                            let ok_state = state_for(&mut states, &mut ip_queue, follow(ip, |_| ()));
+                            let reflect_state = state_for(&mut states, &mut ip_queue, follow(ip, IP::flip));
+
+                            match c {
+                                '}' => {
+                                    actions[state].push(Action::EndBlock(ok_state, reflect_state));
+                                    used_actions.insert(Action::EndBlock(0, 0));
+                                },
+
+                                'u' => {
+                                    actions[state].push(Action::Under(ok_state, reflect_state));
+                                    used_actions.insert(Action::Under(0, 0));
+                                },
+
+                                'i' => {
+                                    let next = ip.new_straight(width, height, depth);
+                                    actions[state].push(Action::FileInput(ok_state, reflect_state, next.x, next.y, next.z, next.dx, next.dy, next.dz));
+                                    used_actions.extend(runtime::required_actions());
+                                },
+
+                                'o' => {
+                                    actions[state].push(Action::FileOutput(ok_state, reflect_state));
+                                    used_actions.insert(Action::FileOutput(0, 0));
+                                    used_actions.insert(Action::TableGet);
+                                },
+
+                                '(' => {
+                                    actions[state].push(Action::LoadFingerprint(ok_state, reflect_state));
+                                    used_actions.extend(fingerprint::required_actions());
+                                },
+
+                                ')' => {
+                                    actions[state].push(Action::UnloadFingerprint(ok_state, reflect_state));
+                                    used_actions.extend(fingerprint::required_actions());
+                                },
+
+                                _ => {
+                                    actions[state].push(Action::Semantic(c, ok_state, reflect_state));
+                                    used_actions.extend(fingerprint::required_actions());
+                                }
+                            }
+
+                            break
+                        },
+
+                        'p' => {
+                            if self.vars_enabled {
+                                let next = ip.new_straight(width, height, depth);
+                                actions[state].push(Action::TablePut(next.x, next.y, next.z, next.dx, next.dy, next.dz));
+                                used_actions.extend(runtime::required_actions());
+                            } else {
+                                return Err(ParserError::VarsDisabled)
+                            }
+                        },
+
+                        's' => {
+                            if self.vars_enabled {
+                                ip.advance(width, height, depth);
+                                actions[state].push(Action::Store(ip.x, ip.y, ip.z, ip.dx, ip.dy, ip.dz));
+                                used_actions.extend(runtime::required_actions());
+                            } else {
+                                return Err(ParserError::VarsDisabled)
+                            }
+                        },
+
+                        'g' => {
+                            if self.vars_enabled {
+                                actions[state].push(Action::TableGet);
+                                used_actions.insert(Action::TableGet);
+                            } else {
+                                return Err(ParserError::VarsDisabled)
+                            }
+                        },
+
+                        // The new IP goes the other way, starting from the cell behind this one on its first tick
+                        't' => {
+                            let child = state_for(&mut states, &mut ip_queue, Start { ip: ip.new_reverse(width, height, depth), stringmode: false, repeat: None });
+                            actions[state].push(Action::Split(child));
+                            used_actions.insert(Action::Split(0));
+                        },
+
+                        'y' => {
+                            actions[state].push(Action::SystemInfo(ip.x, ip.y, ip.z, ip.dx, ip.dy, ip.dz));
+                            used_actions.extend(runtime::required_actions());
+                        },
+
+                        ' ' | 'z' => (),
+
+                        c => {
+                            if !self.exit_on_invalid {
+                                return Err(ParserError::UnexpectedChar(ip.x, ip.y, c))
+                            }
+                        }
+                    }
+                }
+
+                if repeat.is_some() {
+                    let next = state_for(&mut states, &mut ip_queue, Start { ip, stringmode, repeat });
+                    actions[state].push(Action::CallState(next));
+                    used_actions.insert(Action::CallState(0));
+                    break
+                }
+
+                match iterate {
+                    Some((c, n)) if n > 0 => iterate = Some((c, n - 1)),
+
+                    _ => {
+                        iterate = None;
+                        ip.advance(width, height, depth)
+                    }
+                }
+
+                if concurrent {
+                    let next = state_for(&mut states, &mut ip_queue, Start { ip, stringmode, repeat: None });
+                    actions[state].push(Action::CallState(next));
+                    used_actions.insert(Action::CallState(0));
+                    break
+                }
+            }
+
+            state += 1;
+        }
+
+        Ok(StateGraph {
+            code,
+            states: actions,
+            used_actions,
+            compiled,
+            starts: ip_queue.iter().map(|start| (start.ip, start.stringmode, start.repeat.is_some())).collect()
+        })
+    }
+
+    fn write_first<W: Write>(&self, writer: &mut W, code: &[Vec<Vec<char>>], compiled: &[Vec<Vec<bool>>], starts: &[(IP, bool, bool)], used_actions: &HashSet<Action>) -> io::Result<()> {
+        let output = used_actions.contains(&Action::OutputChar) || used_actions.contains(&Action::OutputNumber);
+        let input = used_actions.contains(&Action::InputChar) || used_actions.contains(&Action::InputNumber);
+        let table = used_actions.contains(&Action::TableGet) || used_actions.contains(&Action::TablePut(0, 0, 0, 0, 0, 0));
+        let random = used_actions.contains(&Action::Random(Vec::new()));
+        let fingerprints = used_actions.contains(&Action::LoadFingerprint(0, 0));
+        let repeat = used_actions.contains(&Action::Repeat(' '));
+        let timer = used_actions.contains(&Action::Call("hrti_mark"));
+        let blocks = used_actions.contains(&Action::BeginBlock(0, 0, 0)) || used_actions.contains(&Action::EndBlock(0, 0)) || used_actions.contains(&Action::Under(0, 0));
+        let interpreter = used_actions.contains(&Action::Interpret(0, 0, 0, 0, 0, 0));
+        let split = used_actions.contains(&Action::Split(0));
+        let threads = split || interpreter;
+
+        // Everything each IP has its own copy of
+        let mut per_ip = vec![("stack", "Vec<i64>")];
+
+        if blocks {
+            per_ip.push(("stacks", "Vec<Vec<i64>>"));
+        }
+
+        if table || blocks {
+            per_ip.push(("offset", "(i64, i64, i64)"));
+        }
+
+        if fingerprints {
+            per_ip.push(("semantics", "Vec<Vec<i64>>"));
+        }
+
+        if repeat {
+            per_ip.push(("iterate", "Option<(i64, i64)>"));
+        }
+
+        if timer {
+            per_ip.push(("mark", "Option<std::time::Instant>"));
+        }
+
+        if table {
+            writeln!(writer, "use std::collections::HashMap;")?;
+        }
+
+        if threads {
+            writeln!(writer, "use std::collections::VecDeque;")?;
+        }
+
+        if random {
+            writeln!(writer, "use std::collections::hash_map::RandomState;")?;
+            writeln!(writer, "use std::hash::{{BuildHasher, Hasher}};")?;
+        }
+
+        match (input, output) {
+            (true, true) => writeln!(writer, "use std::io::{{self, Read, Write}};")?,
+            (true, false) => writeln!(writer, "use std::io::{{self, Read}};")?,
+            (false, true) => writeln!(writer, "use std::io::{{self, Write}};")?,
+            (false, false) => ()
+        }
+
+        writeln!(writer)?;
+
+        if table {
+            runtime::write_grid(writer, code)?;
+        }
+
+        if used_actions.contains(&Action::TablePut(0, 0, 0, 0, 0, 0)) {
+            runtime::write_compiled(writer, compiled)?;
+        }
+
+        if table || blocks {
+            writeln!(writer, "const DIMENSIONS: i64 = {};\n", self.dimensions)?;
+        }
+
+        if blocks {
+            writeln!(writer, "const MAX_PADDING: u64 = {};\n", runtime::MAX_PADDING)?;
+        }
+
+        if table {
+            writeln!(writer, "const BEFUNGE93: bool = {};\n", self.std == Standard::Befunge93)?;
+        }
+
+        if interpreter {
+            writeln!(writer, "const EXIT_ON_INVALID: bool = {};\n", self.exit_on_invalid)?;
+        }
+
+        if used_actions.contains(&Action::SystemInfo(0, 0, 0, 0, 0, 0)) {
+            writeln!(writer, "const HANDPRINT: i64 = {:#x};", self.handprint)?;
+            writeln!(writer, "const VERSION: i64 = {};\n", runtime::version())?;
+        }
+
+        if fingerprints {
+            let ids: Vec<String> = self.fingerprints.iter().map(|id| format!("{:#x}", id)).collect();
+            writeln!(writer, "const FINGERPRINTS: &[i64] = &[{}];\n", ids.join(", "))?;
+        }
+
+        if used_actions.contains(&Action::FileInput(0, 0, 0, 0, 0, 0, 0, 0)) || used_actions.contains(&Action::FileOutput(0, 0)) {
+            writeln!(writer, "const FILE_IO_ENABLED: bool = {};\n", self.file_io_enabled)?;
+        }
+
+        if used_actions.contains(&Action::Execute) {
+            writeln!(writer, "const EXECUTE_ENABLED: bool = {};\n", self.execute_enabled)?;
+        }
+
+        if interpreter {
+            runtime::write_state_ips(writer, starts)?;
+        }
+
+        if random {
+            runtime::write_rng(writer)?;
+        }
+
+        if threads {
+            runtime::write_thread(writer, &per_ip, interpreter)?;
+        }
+
+        writeln!(writer, "struct Program {{")?;
+        writeln!(writer, "    stack: Vec<i64>,")?;
+
+        if output {
+            writeln!(writer, "    output: io::Stdout,")?;
+        }
+
+        if input {
+            writeln!(writer, "    input: io::Bytes<io::StdinLock<'static>>,")?;
+        }
+
+        if random {
+            writeln!(writer, "    rng: Rng,")?;
+        }
+
+        if table {
+            writeln!(writer, "    table: HashMap<(i64, i64, i64), i64>,")?;
+        }
+
+        if blocks {
+            writeln!(writer, "    stacks: Vec<Vec<i64>>,")?;
+        }
+
+        if table || blocks {
+            writeln!(writer, "    offset: (i64, i64, i64),")?;
+        }
+
+        if fingerprints {
+            writeln!(writer, "    semantics: Vec<Vec<i64>>,")?;
+        }
+
+        if repeat {
+            writeln!(writer, "    iterate: Option<(i64, i64)>,")?;
+        }
+
+        if timer {
+            writeln!(writer, "    mark: Option<std::time::Instant>,")?;
+        }
+
+        if threads {
+            writeln!(writer, "    id: i64,")?;
+            writeln!(writer, "    next_id: i64,")?;
+            writeln!(writer, "    threads: VecDeque<Thread>,")?;
+        }
+
+        writeln!(writer, "}}\n")?;
+
+        writeln!(writer, "impl Program {{")?;
+        writeln!(writer, "    fn run() {{")?;
+        writeln!(writer, "        let mut p = Program {{")?;
+        writeln!(writer, "            stack: Vec::new(),")?;
+
+        if output {
+            writeln!(writer, "            output: io::stdout(),")?;
+        }
+
+        if input {
+            writeln!(writer, "            input: io::stdin().lock().bytes(),")?;
+        }
+
+        if random {
+            writeln!(writer, "            rng: Rng::new(),")?;
+        }
+
+        if table {
+            writeln!(writer, "            table: HashMap::new(),")?;
+        }
+
+        if blocks {
+            writeln!(writer, "            stacks: Vec::new(),")?;
+        }
+
+        if table || blocks {
+            writeln!(writer, "            offset: (0, 0, 0),")?;
+        }
+
+        if fingerprints {
+            writeln!(writer, "            semantics: vec![Vec::new(); 26],")?;
+        }
+
+        if repeat {
+            writeln!(writer, "            iterate: None,")?;
+        }
+
+        if timer {
+            writeln!(writer, "            mark: None,")?;
+        }
+
+        if threads {
+            writeln!(writer, "            id: 0,")?;
+            writeln!(writer, "            next_id: 0,")?;
+            writeln!(writer, "            threads: VecDeque::new(),")?;
+        }
+
+        writeln!(writer, "        }};")?;
+        writeln!(writer)?;
+
+        if split {
+            // Every state is a single tick, after which the next IP in the queue takes over
+            writeln!(writer, "        let mut state = 0;")?;
+            writeln!(writer, "        loop {{")?;
+            writeln!(writer, "            let next = match state {{")?;
+            for s in 0..starts.len() {
+                writeln!(writer, "                {} => p.state{}(),", s, s)?;
+            }
+            writeln!(writer, "                _ => unreachable!()")?;
+            writeln!(writer, "            }};")?;
+            writeln!(writer)?;
+            writeln!(writer, "            state = match p.switch(next) {{")?;
+            writeln!(writer, "                Some(s) => s,")?;
+            writeln!(writer, "                None => break")?;
+            writeln!(writer, "            }};")?;
+            writeln!(writer, "        }}")?;
+        } else {
+            writeln!(writer, "        let mut state = Some(0);")?;
+            writeln!(writer, "        while let Some(s) = state {{")?;
+            writeln!(writer, "            state = match s {{")?;
+            for s in 0..starts.len() {
+                writeln!(writer, "                {} => p.state{}(),", s, s)?;
+            }
+            writeln!(writer, "                _ => unreachable!()")?;
+            writeln!(writer, "            }};")?;
+            writeln!(writer, "        }}")?;
+        }
+
+        if output {
+            writeln!(writer)?;
+            writeln!(writer, "        let _ = p.output.flush();")?;
+        }
+
+        writeln!(writer, "    }}")?;
+
+        if input {
+            runtime::write_read_char(writer)?;
+        }
+
+        if table || blocks {
+            runtime::write_vectors(writer)?;
+        }
+
+        if threads {
+            runtime::write_threads(writer, &per_ip, interpreter, split)?;
+        }
+
+        for action in used_actions.iter() {
+            action.write_impl_to(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_end<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "}}
+
+fn main() {{
+    Program::run()
+}}")
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, graph: &StateGraph) -> Result<(), ParserError> {
+        let unsupported = match self.emit {
+            Emit::Rust => None,
+            Emit::LlvmIr => graph.used_actions.iter().find(|act| !llvm::supports(act)).map(|act| ("LLVM IR", act)),
+            Emit::C => graph.used_actions.iter().find(|act| !c::supports(act)).map(|act| ("C", act))
+        };
+
+        if let Some((target, act)) = unsupported {
+            return Err(ParserError::UnsupportedAction(target.to_string(), act.clone()))
+        }
+
+        self.write_program(writer, graph)
+            .map_err(|_| ParserError::OutputError)
+    }
+
+    fn write_program<W: Write>(&self, writer: &mut W, graph: &StateGraph) -> io::Result<()> {
+        let (actions, used_actions) = (&graph.states, &graph.used_actions);
+
+        match self.emit {
+            Emit::Rust => {
+                self.write_first(writer, &graph.code, &graph.compiled, &graph.starts, used_actions)?;
+
+                for (state, vec) in actions.iter().enumerate() {
+                    writeln!(writer, "\n    fn state{}(&mut self) -> Option<usize> {{", state)?;
+                    for action in vec.iter() {
+                        action.write_to(writer)?;
+                    }
+                    writeln!(writer, "    }}")?;
+                }
+
+                self.write_end(writer)
+            },
+
+            Emit::LlvmIr => {
+                llvm::write_first(writer, used_actions)?;
+                for (state, vec) in actions.iter().enumerate() {
+                    llvm::write_state(writer, state, vec)?;
+                }
+                llvm::write_end(writer)
+            },
+
+            Emit::C => {
+                c::write_first(writer, used_actions)?;
+                for (state, vec) in actions.iter().enumerate() {
+                    c::write_state(writer, state, vec)?;
+                }
+                c::write_end(writer)
+            }
+        }
+    }
+}
+
+// Where a state starts, and the instruction 'k' repeats there if the state is one of its repetitions
+#[derive(Clone, Copy)]
+struct Start {
+    ip: IP,
+    stringmode: bool,
+    repeat: Option<char>
+}
+
+// The states starting at a cell, by the delta, stringmode and repeated instruction the IP enters with
+type StateMap = HashMap<((i64, i64, i64), bool, Option<char>), usize>;
+
+// Finds the state for the given start, queueing a new one if none has been made yet
+fn state_for(states: &mut [Vec<Vec<StateMap>>], queue: &mut Vec<Start>, start: Start) -> usize {
+    let ip = start.ip;
+
+    match states[ip.z as usize][ip.y as usize][ip.x as usize].entry((ip.delta(), start.stringmode, start.repeat)) {
+        Entry::Vacant(entry) => {
+            entry.insert(queue.len());
+            queue.push(start);
+
+            queue.len() - 1
+        },
+
+        Entry::Occupied(entry) => *entry.get()
+    }
+}
+
+// Moves the IP past any spaces and ';' comments, giving up after a full lap of the grid in case it only meets empty cells
+fn skip_free(code: &[Vec<Vec<char>>], compiled: &mut [Vec<Vec<bool>>], ip: &mut IP, (width, height, depth): (usize, usize, usize)) {
+    for _ in 0..width * height * depth {
+        match code[ip.z as usize][ip.y as usize][ip.x as usize] {
+            ' ' => (),
+
+            ';' => loop {
+                ip.advance(width, height, depth);
+                compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+
+                if code[ip.z as usize][ip.y as usize][ip.x as usize] == ';' {
+                    break
+                }
+            },
+
+            _ => return
+        }
+
+        compiled[ip.z as usize][ip.y as usize][ip.x as usize] = true;
+        ip.advance(width, height, depth);
+    }
+}
+
+// Instructions that only touch the stack or I/O, which 'k' can repeat with a runtime loop
+fn simple_action(c: char) -> Option<Action> {
+    match c {
+        '0'..='9' => Some(Action::PushNumber(c.to_digit(10).unwrap() as i64)),
+        'a'..='f' => Some(Action::PushNumber(c.to_digit(16).unwrap() as i64)),
+        '~' => Some(Action::InputChar),
+        '&' => Some(Action::InputNumber),
+        ',' => Some(Action::OutputChar),
+        '.' => Some(Action::OutputNumber),
+        '+' => Some(Action::Add),
+        '-' => Some(Action::Subtract),
+        '*' => Some(Action::Multiply),
+        '/' => Some(Action::Divide),
+        '%' => Some(Action::Modulo),
+        '!' => Some(Action::Not),
+        '`' => Some(Action::Greater),
+        ':' => Some(Action::Duplicate),
+        '$' => Some(Action::Pop),
+        '\\' => Some(Action::Swap),
+        'n' => Some(Action::Clear),
+        _ => None
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
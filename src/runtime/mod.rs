@@ -0,0 +1,880 @@
+use std::io::{self, Write};
+
+use crate::action::Action;
+use crate::ip::IP;
+use crate::fingerprint;
+
+// Actions needed to fall back to the embedded interpreter, including the helpers it calls into
+pub fn required_actions() -> Vec<Action> {
+    let mut actions = vec![
+        Action::Interpret(0, 0, 0, 0, 0, 0),
+        Action::Repeat(' '),
+        Action::TablePut(0, 0, 0, 0, 0, 0),
+        Action::Store(0, 0, 0, 0, 0, 0),
+        Action::BeginBlock(0, 0, 0),
+        Action::EndBlock(0, 0),
+        Action::Under(0, 0),
+        Action::SystemInfo(0, 0, 0, 0, 0, 0),
+        Action::Quit,
+        Action::Execute,
+        Action::FileInput(0, 0, 0, 0, 0, 0, 0, 0),
+        Action::FileOutput(0, 0),
+        Action::InputChar,
+        Action::InputNumber,
+        Action::OutputChar,
+        Action::OutputNumber,
+        Action::Duplicate,
+        Action::Add,
+        Action::Subtract,
+        Action::Multiply,
+        Action::Divide,
+        Action::Modulo,
+        Action::Not,
+        Action::Greater,
+        Action::Swap,
+        Action::TableGet,
+        Action::Random(Vec::new())
+    ];
+
+    actions.extend(fingerprint::required_actions());
+    actions
+}
+
+pub fn write_grid<W: Write>(writer: &mut W, code: &[Vec<Vec<char>>]) -> io::Result<()> {
+    writeln!(writer, "const WIDTH: i64 = {};", code[0][0].len())?;
+    writeln!(writer, "const HEIGHT: i64 = {};", code[0].len())?;
+    writeln!(writer, "const DEPTH: i64 = {};", code.len())?;
+    writeln!(writer)?;
+    writeln!(writer, "static CODE: &[&[&[i64]]] = &[")?;
+    for plane in code {
+        writeln!(writer, "    &[")?;
+        for row in plane {
+            let cells: Vec<String> = row.iter().map(|c| (*c as i64).to_string()).collect();
+            writeln!(writer, "        &[{}],", cells.join(", "))?;
+        }
+        writeln!(writer, "    ],")?;
+    }
+    writeln!(writer, "];\n")
+}
+
+// How many zeroes a stack-stack instruction pads with at most when asked to move more values than there are
+pub const MAX_PADDING: u64 = 1 << 16;
+
+// The crate version packed as major * 10000 + minor * 100 + patch for 'y'
+pub fn version() -> i64 {
+    env!("CARGO_PKG_VERSION").split('.')
+        .map(|part| part.parse::<i64>().unwrap_or(0))
+        .fold(0, |version, part| version * 100 + part)
+}
+
+pub fn write_compiled<W: Write>(writer: &mut W, compiled: &[Vec<Vec<bool>>]) -> io::Result<()> {
+    writeln!(writer, "static COMPILED: &[&[&[bool]]] = &[")?;
+    for plane in compiled {
+        writeln!(writer, "    &[")?;
+        for row in plane {
+            let cells: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+            writeln!(writer, "        &[{}],", cells.join(", "))?;
+        }
+        writeln!(writer, "    ],")?;
+    }
+    writeln!(writer, "];\n")
+}
+
+// xorshift64* seeded from the standard library's per-process random hasher keys,
+// so generated programs need nothing beyond std
+pub fn write_rng<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", RNG)
+}
+
+pub fn write_read_char<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", READ_CHAR)
+}
+
+// Positions are always kept in three components, but only DIMENSIONS of them live on the stack
+pub fn write_vectors<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", VECTORS)
+}
+
+pub fn write_interpreter<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", INTERPRETER)
+}
+
+// The embedded interpreter takes IPs queued by compiled states from where their state starts,
+// with whether it's in stringmode and whether it repeats an instruction for 'k'
+pub fn write_state_ips<W: Write>(writer: &mut W, starts: &[(IP, bool, bool)]) -> io::Result<()> {
+    writeln!(writer, "static STATE_IPS: &[(i64, i64, i64, i64, i64, i64, bool, bool)] = &[")?;
+    for (ip, stringmode, repeating) in starts {
+        writeln!(writer, "    ({}, {}, {}, {}, {}, {}, {}, {}),", ip.x, ip.y, ip.z, ip.dx, ip.dy, ip.dz, stringmode, repeating)?;
+    }
+    writeln!(writer, "];\n")
+}
+
+// An IP waiting for its turn holds its own copy of the given Program fields, and either the state
+// it carries on from or, once the embedded interpreter has run it, its place on the grid
+pub fn write_thread<W: Write>(writer: &mut W, fields: &[(&str, &str)], interpreter: bool) -> io::Result<()> {
+    writeln!(writer, "struct Thread {{")?;
+    writeln!(writer, "    id: i64,")?;
+    writeln!(writer, "    state: usize,")?;
+
+    if interpreter {
+        writeln!(writer, "    ip: Option<(i64, i64, i64, i64, i64, i64, bool)>,")?;
+    }
+
+    for (name, kind) in fields {
+        writeln!(writer, "    {}: {},", name, kind)?;
+    }
+
+    writeln!(writer, "}}\n")
+}
+
+pub fn write_threads<W: Write>(writer: &mut W, fields: &[(&str, &str)], interpreter: bool, split: bool) -> io::Result<()> {
+    writeln!(writer)?;
+    writeln!(writer, "    // Swaps the running IP with a waiting one")?;
+    writeln!(writer, "    fn swap_thread(&mut self, thread: &mut Thread) {{")?;
+    writeln!(writer, "        std::mem::swap(&mut self.id, &mut thread.id);")?;
+    for (name, _) in fields {
+        writeln!(writer, "        std::mem::swap(&mut self.{}, &mut thread.{});", name, name)?;
+    }
+    writeln!(writer, "    }}")?;
+
+    writeln!(writer)?;
+    writeln!(writer, "    // Queues a copy of the running IP that starts at the given state, with a count from 'k' left behind")?;
+    writeln!(writer, "    fn split(&mut self, state: usize) {{")?;
+    writeln!(writer, "        self.next_id += 1;")?;
+    writeln!(writer, "        self.threads.push_back(Thread {{")?;
+    writeln!(writer, "            id: self.next_id,")?;
+    writeln!(writer, "            state,")?;
+
+    if interpreter {
+        writeln!(writer, "            ip: None,")?;
+    }
+
+    for (name, _) in fields {
+        if *name == "iterate" {
+            writeln!(writer, "            iterate: None,")?;
+        } else {
+            writeln!(writer, "            {}: self.{}.clone(),", name, name)?;
+        }
+    }
+
+    writeln!(writer, "        }});")?;
+    writeln!(writer, "    }}")?;
+
+    if split {
+        writeln!(writer, "{}", SWITCH)?;
+    }
+
+    Ok(())
+}
+
+// Fills 'y' from the running program's own arguments, environment and clock
+pub fn write_system_info<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", SYSTEM_INFO)
+}
+
+// Runs the popped string through the shell, unless the compiler was told to leave '=' disabled
+pub fn write_execute<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", EXECUTE)
+}
+
+// 'i' and 'o' share the table with 'p' and 'g', and reflect unless the compiler enabled file access
+pub fn write_file_input<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", FILE_INPUT)
+}
+
+pub fn write_file_output<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", FILE_OUTPUT)
+}
+
+// The top of the stack stack stays in `stack` so the other helpers don't need to know about blocks
+pub fn write_begin_block<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", BEGIN_BLOCK)
+}
+
+pub fn write_end_block<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", END_BLOCK)
+}
+
+pub fn write_under<W: Write>(writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "{}", UNDER)
+}
+
+const RNG: &str = r#"struct Rng(u64);
+
+impl Rng {
+    fn new() -> Rng {
+        let seed = RandomState::new().build_hasher().finish();
+        Rng(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+}
+"#;
+
+const READ_CHAR: &str = r#"
+    fn read_char(&mut self) -> Option<char> {
+        let first = self.input.next()?.ok()?;
+        let len = match first {
+            0xf0..=0xf7 => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1
+        };
+
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            bytes.push(self.input.next()?.ok()?);
+        }
+
+        Some(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }"#;
+
+const VECTORS: &str = r#"
+    fn pop_vector(&mut self) -> (i64, i64, i64) {
+        let z = if DIMENSIONS > 2 { self.stack.pop().unwrap_or(0) } else { 0 };
+        let y = if DIMENSIONS > 1 { self.stack.pop().unwrap_or(0) } else { 0 };
+        let x = self.stack.pop().unwrap_or(0);
+        (x, y, z)
+    }
+
+    fn push_vector(&mut self, (x, y, z): (i64, i64, i64)) {
+        self.stack.extend([x, y, z].iter().take(DIMENSIONS as usize));
+    }"#;
+
+const INTERPRETER: &str = r#"
+    fn step(&self, mut x: i64, mut y: i64, mut z: i64, dx: i64, dy: i64, dz: i64) -> (i64, i64, i64) {
+        let in_bounds = |x: i64, y: i64, z: i64| x >= 0 && y >= 0 && z >= 0 && x < WIDTH && y < HEIGHT && z < DEPTH;
+
+        if in_bounds(x + dx, y + dy, z + dz) {
+            return (x + dx, y + dy, z + dz)
+        }
+
+        while in_bounds(x - dx, y - dy, z - dz) {
+            x -= dx;
+            y -= dy;
+            z -= dz;
+        }
+
+        (x, y, z)
+    }
+
+    // Characters the compiler would have rejected stop the program once they're reached
+    fn fail(&self, message: &str) -> ! {
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        eprintln!("Error: {}", message);
+        std::process::exit(1)
+    }
+
+    // Moves past any spaces and ';' comments, giving up after a full lap of the grid in case there are only empty cells
+    fn skip_free(&self, mut x: i64, mut y: i64, mut z: i64, dx: i64, dy: i64, dz: i64) -> (i64, i64, i64) {
+        for _ in 0..WIDTH * HEIGHT * DEPTH {
+            let c = self.cell(x, y, z);
+
+            if c == ';' as i64 {
+                loop {
+                    (x, y, z) = self.step(x, y, z, dx, dy, dz);
+
+                    if self.cell(x, y, z) == ';' as i64 {
+                        break
+                    }
+                }
+            } else if c != ' ' as i64 {
+                break
+            }
+
+            (x, y, z) = self.step(x, y, z, dx, dy, dz)
+        }
+
+        (x, y, z)
+    }
+
+    // Where an IP queued by a compiled state carries on in the embedded interpreter
+    fn resume(&mut self, state: usize) -> (i64, i64, i64, i64, i64, i64, bool) {
+        let (x, y, z, dx, dy, dz, stringmode, repeating) = STATE_IPS[state];
+
+        if !repeating {
+            return (x, y, z, dx, dy, dz, stringmode)
+        }
+
+        match self.iterate {
+            Some((c, n)) if n > 0 => self.iterate = Some((c, n - 1)),
+
+            _ => {
+                self.iterate = None;
+                let (x, y, z) = self.step(x, y, z, dx, dy, dz);
+                return (x, y, z, dx, dy, dz, false)
+            }
+        }
+
+        (x, y, z, dx, dy, dz, false)
+    }
+
+    // Runs every IP to the end, starting with the current one at the given position. If it has
+    // already had its turn this tick, the IPs waiting after it go first.
+    fn interpret(&mut self, mut x: i64, mut y: i64, mut z: i64, mut dx: i64, mut dy: i64, mut dz: i64, mut yielding: bool) {
+        let mut stringmode = false;
+        // A count left running by a compiled 'k' carries on from here
+        let mut iterate = self.iterate.take();
+        let mut alive = true;
+
+        loop {
+            // Each IP runs a single instruction per tick, in the order they were queued
+            if yielding {
+                match self.threads.pop_front() {
+                    Some(mut next) => {
+                        self.iterate = iterate;
+                        self.swap_thread(&mut next);
+
+                        let ip = match next.ip {
+                            Some(ip) => ip,
+                            None => self.resume(next.state)
+                        };
+
+                        next.ip = Some((x, y, z, dx, dy, dz, stringmode));
+                        (x, y, z, dx, dy, dz, stringmode) = ip;
+                        iterate = self.iterate.take();
+
+                        if alive {
+                            self.threads.push_back(next);
+                        }
+                    },
+
+                    None if alive => (),
+                    None => return
+                }
+            }
+
+            yielding = true;
+            alive = true;
+
+            // Spaces and comments take no time, so every tick runs an actual instruction
+            if !stringmode && iterate.is_none() {
+                (x, y, z) = self.skip_free(x, y, z, dx, dy, dz);
+            }
+
+            let c = match iterate {
+                Some((c, _)) => c,
+                None => self.cell(x, y, z)
+            };
+
+            if stringmode {
+                if c == '"' as i64 {
+                    stringmode = false
+                } else {
+                    self.stack.push(c)
+                }
+            } else {
+                match u32::try_from(c).ok().and_then(char::from_u32).unwrap_or(' ') {
+                    // Instructions added by Funge-98 are just invalid characters to Befunge-93
+                    c if BEFUNGE93 && !"0123456789+-*/%!`><^v?_|\":\\$.,#gp&~@ ".contains(c) => if !EXIT_ON_INVALID {
+                        self.fail(&format!("Instruction '{}' does not exist in Befunge-93", c))
+                    },
+
+                    c @ ('^' | 'v' | '|' | '[' | ']' | 'w') if DIMENSIONS < 2 => if !EXIT_ON_INVALID {
+                        self.fail(&format!("Instruction '{}' does not exist in Unefunge-98", c))
+                    },
+
+                    '>' => { dx = 1; dy = 0; dz = 0 },
+                    '<' => { dx = -1; dy = 0; dz = 0 },
+                    '^' => { dx = 0; dy = -1; dz = 0 },
+                    'v' => { dx = 0; dy = 1; dz = 0 },
+                    '[' => { let t = dx; dx = -dy; dy = t },
+                    ']' => { let t = dx; dx = dy; dy = -t },
+                    'r' => { dx = -dx; dy = -dy; dz = -dz },
+                    'h' if DIMENSIONS > 2 => { dx = 0; dy = 0; dz = 1 },
+                    'l' if DIMENSIONS > 2 => { dx = 0; dy = 0; dz = -1 },
+                    '#' => { (x, y, z) = self.step(x, y, z, dx, dy, dz) },
+                    '"' => stringmode = true,
+
+                    ';' => loop {
+                        (x, y, z) = self.step(x, y, z, dx, dy, dz);
+
+                        if self.cell(x, y, z) == ';' as i64 {
+                            break
+                        }
+                    },
+
+                    d @ '0'..='9' => self.stack.push(d.to_digit(10).unwrap() as i64),
+                    h @ 'a'..='f' => self.stack.push(h.to_digit(16).unwrap() as i64),
+
+                    '~' => self.input_char(),
+                    '&' => self.input_number(),
+                    ',' => self.output_char(),
+                    '.' => self.output_number(),
+                    '+' => self.add(),
+                    '-' => self.subtract(),
+                    '*' => self.multiply(),
+                    '/' => self.divide(),
+                    '%' => self.modulo(),
+                    '!' => self.not(),
+                    '`' => self.greater(),
+                    ':' => self.duplicate(),
+                    '$' => { self.stack.pop(); },
+                    '\\' => self.swap(),
+                    'n' => self.stack.clear(),
+
+                    '\'' => {
+                        (x, y, z) = self.step(x, y, z, dx, dy, dz);
+
+                        let value = self.cell(x, y, z);
+                        self.stack.push(value);
+                    },
+
+                    '?' => match self.rng.next() % (2 * DIMENSIONS as u64) {
+                        0 => { dx = 1; dy = 0; dz = 0 },
+                        1 => { dx = -1; dy = 0; dz = 0 },
+                        2 => { dx = 0; dy = 1; dz = 0 },
+                        3 => { dx = 0; dy = -1; dz = 0 },
+                        4 => { dx = 0; dy = 0; dz = 1 },
+                        _ => { dx = 0; dy = 0; dz = -1 }
+                    },
+
+                    // The path wraps around to where it started, so only the count modulo its length is walked
+                    'j' => {
+                        let n = self.stack.pop().unwrap_or(0);
+                        let (mut lx, mut ly, mut lz) = self.step(x, y, z, dx, dy, dz);
+                        let mut len = 1;
+
+                        while (lx, ly, lz) != (x, y, z) {
+                            (lx, ly, lz) = self.step(lx, ly, lz, dx, dy, dz);
+                            len += 1;
+                        }
+
+                        for _ in 0..n.rem_euclid(len) {
+                            (x, y, z) = self.step(x, y, z, dx, dy, dz);
+                        }
+                    },
+
+                    '_' => match self.stack.pop() {
+                        Some(0) | None => { dx = 1; dy = 0; dz = 0 },
+                        Some(_) => { dx = -1; dy = 0; dz = 0 }
+                    },
+
+                    '|' => match self.stack.pop() {
+                        Some(0) | None => { dx = 0; dy = 1; dz = 0 },
+                        Some(_) => { dx = 0; dy = -1; dz = 0 }
+                    },
+
+                    'm' if DIMENSIONS > 2 => match self.stack.pop() {
+                        Some(0) | None => { dx = 0; dy = 0; dz = -1 },
+                        Some(_) => { dx = 0; dy = 0; dz = 1 }
+                    },
+
+                    'w' => match (self.stack.pop().unwrap_or(0), self.stack.pop().unwrap_or(0)) {
+                        (a, b) if a < b => { let t = dx; dx = -dy; dy = t },
+                        (a, b) if a > b => { let t = dx; dx = dy; dy = -t },
+                        _ => ()
+                    },
+
+                    'k' => {
+                        let n = self.stack.pop().unwrap_or(0);
+
+                        loop {
+                            (x, y, z) = self.step(x, y, z, dx, dy, dz);
+
+                            let c = self.cell(x, y, z);
+
+                            if c == ';' as i64 {
+                                loop {
+                                    (x, y, z) = self.step(x, y, z, dx, dy, dz);
+
+                                    if self.cell(x, y, z) == ';' as i64 {
+                                        break
+                                    }
+                                }
+                            } else if c != ' ' as i64 {
+                                break
+                            }
+                        }
+
+                        if n > 0 {
+                            iterate = Some((self.cell(x, y, z), n))
+                        }
+                    },
+
+                    'x' => (dx, dy, dz) = self.pop_vector(),
+
+                    '{' => {
+                        let next = self.step(x, y, z, dx, dy, dz);
+                        self.begin_block(next)
+                    },
+
+                    '}' => if !self.end_block() { dx = -dx; dy = -dy; dz = -dz },
+                    'u' => if !self.under() { dx = -dx; dy = -dy; dz = -dz },
+
+                    '(' => if !self.load_fingerprint() { dx = -dx; dy = -dy; dz = -dz },
+                    ')' => if !self.unload_fingerprint() { dx = -dx; dy = -dy; dz = -dz },
+                    c @ 'A'..='Z' => if !self.semantic(c) { dx = -dx; dy = -dy; dz = -dz },
+
+                    's' => {
+                        (x, y, z) = self.step(x, y, z, dx, dy, dz);
+                        self.store(x, y, z);
+                    },
+
+                    'z' => (),
+                    'y' => self.system_info((x, y, z), (dx, dy, dz)),
+
+                    // The new IP is queued the way a compiled state queues one, then placed on the grid instead
+                    't' => {
+                        let (cx, cy, cz) = self.step(x, y, z, -dx, -dy, -dz);
+                        self.split(0);
+
+                        if let Some(thread) = self.threads.back_mut() {
+                            thread.ip = Some((cx, cy, cz, -dx, -dy, -dz, false));
+                        }
+                    },
+
+                    'p' => { self.table_put(); },
+                    'g' => self.table_get(),
+                    '@' => alive = false,
+                    'q' => self.quit(),
+                    '=' => self.execute(),
+                    'i' => if self.file_input().is_none() { dx = -dx; dy = -dy; dz = -dz },
+                    'o' => if !self.file_output() { dx = -dx; dy = -dy; dz = -dz },
+                    ' ' => (),
+
+                    c => if !EXIT_ON_INVALID {
+                        self.fail(&format!("Unexpected char at ({}, {}): {}", x, y, c))
+                    }
+                }
+            }
+
+            if alive {
+                match iterate {
+                    Some((c, n)) if n > 0 => iterate = Some((c, n - 1)),
+
+                    _ => {
+                        iterate = None;
+                        (x, y, z) = self.step(x, y, z, dx, dy, dz)
+                    }
+                }
+            }
+        }
+    }"#;
+
+const SWITCH: &str = r#"
+    // Hands the next tick to the IP at the front of the queue, and returns the state it carries on
+    // from. The running IP goes to the back of the queue unless it has ended.
+    fn switch(&mut self, state: Option<usize>) -> Option<usize> {
+        let mut next = match self.threads.pop_front() {
+            Some(next) => next,
+            None => return state
+        };
+
+        self.swap_thread(&mut next);
+        let resume = next.state;
+
+        if let Some(state) = state {
+            next.state = state;
+            self.threads.push_back(next);
+        }
+
+        Some(resume)
+    }"#;
+
+const SYSTEM_INFO: &str = r#"
+    fn system_info(&mut self, position: (i64, i64, i64), delta: (i64, i64, i64)) {
+        let n = self.stack.pop().unwrap_or(0);
+        let len = self.stack.len();
+
+        let push_string = |stack: &mut Vec<i64>, s: &str| {
+            stack.push(0);
+            stack.extend(s.chars().rev().map(|c| c as i64));
+        };
+
+        self.stack.push(0);
+        for (key, value) in std::env::vars().collect::<Vec<_>>().iter().rev() {
+            push_string(&mut self.stack, &format!("{}={}", key, value));
+        }
+
+        self.stack.push(0);
+        for arg in std::env::args().collect::<Vec<_>>().iter().rev() {
+            push_string(&mut self.stack, arg);
+        }
+
+        for i in 0..self.stacks.len() {
+            let size = self.stacks[i].len() as i64;
+            self.stack.push(size);
+        }
+        self.stack.push(len as i64);
+        self.stack.push(self.stacks.len() as i64 + 1);
+
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let (days, time) = (secs / 86400, secs % 86400);
+
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        self.stack.push(time / 3600 * 256 * 256 + time / 60 % 60 * 256 + time % 60);
+        self.stack.push((year - 1900) * 256 * 256 + month * 256 + day);
+
+        let mut bounds: Option<((i64, i64, i64), (i64, i64, i64))> = None;
+        let points = (0..DEPTH).flat_map(|z| (0..HEIGHT).flat_map(move |y| (0..WIDTH).map(move |x| (x, y, z))))
+            .chain(self.table.keys().copied())
+            .filter(|&(x, y, z)| self.cell(x, y, z) != ' ' as i64);
+
+        for (x, y, z) in points.collect::<Vec<_>>() {
+            bounds = Some(match bounds {
+                Some(((lx, ly, lz), (gx, gy, gz))) => ((lx.min(x), ly.min(y), lz.min(z)), (gx.max(x), gy.max(y), gz.max(z))),
+                None => ((x, y, z), (x, y, z))
+            });
+        }
+
+        let ((lx, ly, lz), (gx, gy, gz)) = bounds.unwrap_or(((0, 0, 0), (0, 0, 0)));
+        self.push_vector((gx - lx, gy - ly, gz - lz));
+        self.push_vector((lx, ly, lz));
+
+        let offset = self.offset;
+        self.push_vector(offset);
+        self.push_vector(delta);
+        self.push_vector(position);
+        let flags = 0b0001 | if EXECUTE_ENABLED { 0b1000 } else { 0 } | if FILE_IO_ENABLED { 0b0110 } else { 0 };
+        self.stack.extend([0, self.id, DIMENSIONS, std::path::MAIN_SEPARATOR as i64, EXECUTE_ENABLED as i64, VERSION, HANDPRINT, 8, flags]);
+
+        if n > 0 {
+            let value = self.stack.len().checked_sub(n as usize).map_or(0, |i| self.stack[i]);
+            self.stack.truncate(len);
+            self.stack.push(value);
+        }
+    }"#;
+
+const EXECUTE: &str = r#"
+    fn execute(&mut self) {
+        let mut command = String::new();
+
+        loop {
+            match self.stack.pop().unwrap_or(0) {
+                0 => break,
+                c => command.push(u32::try_from(c).ok().and_then(char::from_u32).unwrap_or('\0'))
+            }
+        }
+
+        if !EXECUTE_ENABLED {
+            self.stack.push(-1);
+            return;
+        }
+
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+
+        let status = if cfg!(windows) {
+            std::process::Command::new("cmd").arg("/C").arg(&command).status()
+        } else {
+            std::process::Command::new("sh").arg("-c").arg(&command).status()
+        };
+
+        self.stack.push(status.ok().and_then(|status| status.code()).map_or(-1, |code| code as i64));
+    }"#;
+
+const FILE_INPUT: &str = r#"
+    // Reflects with None, otherwise returns whether the file overwrote any compiled cells
+    fn file_input(&mut self) -> Option<bool> {
+        let mut name = String::new();
+
+        loop {
+            match self.stack.pop().unwrap_or(0) {
+                0 => break,
+                c => name.push(u32::try_from(c).ok().and_then(char::from_u32).unwrap_or('\0'))
+            }
+        }
+
+        let flags = self.stack.pop().unwrap_or(0);
+        let (x, y, z) = self.pop_vector();
+
+        if !FILE_IO_ENABLED {
+            return None;
+        }
+
+        let data = match std::fs::read(&name) {
+            Ok(data) => data,
+            Err(_) => return None
+        };
+
+        let (ox, oy, oz) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));
+        let (mut width, mut height, mut depth) = (0, 0, 0);
+        let mut changed = false;
+
+        if flags & 1 != 0 {
+            for (i, &b) in data.iter().enumerate() {
+                changed |= self.load_cell(ox + i as i64, oy, oz, b as i64);
+            }
+
+            width = data.len() as i64;
+            height = if data.is_empty() { 0 } else { 1 };
+            depth = height;
+        } else {
+            let data: Vec<u8> = if DIMENSIONS > 1 { data } else { data.into_iter().filter(|&b| b != b'\n' && b != b'\r').collect() };
+            let planes: Vec<&[u8]> = if DIMENSIONS > 2 { data.split(|&b| b == b'\x0c').collect() } else { vec![&data] };
+
+            for (plane, data) in planes.into_iter().enumerate() {
+                let data = data.strip_suffix(b"\n").unwrap_or(data);
+
+                for (row, line) in data.split(|&b| b == b'\n').enumerate() {
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+                    for (i, &b) in line.iter().enumerate() {
+                        if b != b' ' {
+                            changed |= self.load_cell(ox + i as i64, oy + row as i64, oz + plane as i64, b as i64);
+                        }
+                    }
+
+                    width = width.max(line.len() as i64);
+                    height = height.max(row as i64 + 1);
+                }
+
+                depth = plane as i64 + 1;
+            }
+        }
+
+        self.push_vector((width, height, depth));
+        self.push_vector((x, y, z));
+        Some(changed)
+    }
+
+    // Like 'p', loading over a compiled cell with a different value invalidates the compiled code
+    fn load_cell(&mut self, x: i64, y: i64, z: i64, value: i64) -> bool {
+        self.table.insert((x, y, z), value);
+
+        let inside = x >= 0 && y >= 0 && z >= 0 && x < WIDTH && y < HEIGHT && z < DEPTH;
+        inside && COMPILED[z as usize][y as usize][x as usize] && CODE[z as usize][y as usize][x as usize] != value
+    }"#;
+
+const FILE_OUTPUT: &str = r#"
+    fn file_output(&mut self) -> bool {
+        let mut name = String::new();
+
+        loop {
+            match self.stack.pop().unwrap_or(0) {
+                0 => break,
+                c => name.push(u32::try_from(c).ok().and_then(char::from_u32).unwrap_or('\0'))
+            }
+        }
+
+        let flags = self.stack.pop().unwrap_or(0);
+        let (x, y, z) = self.pop_vector();
+        let (width, height, depth) = self.pop_vector();
+
+        if !FILE_IO_ENABLED {
+            return false;
+        }
+
+        let (ox, oy, oz) = (x.wrapping_add(self.offset.0), y.wrapping_add(self.offset.1), z.wrapping_add(self.offset.2));
+        let height = if DIMENSIONS > 1 { height } else { 1 };
+        let depth = if DIMENSIONS > 2 { depth } else { 1 };
+
+        let planes: Vec<String> = (0..depth).map(|plane| {
+            let mut lines: Vec<String> = (0..height).map(|row| {
+                (0..width).map(|col| u32::try_from(self.cell(ox + col, oy + row, oz + plane)).ok().and_then(char::from_u32).unwrap_or(' ')).collect()
+            }).collect();
+
+            // Linear text drops the trailing spaces and blank lines the box padded out
+            if flags & 1 != 0 {
+                for line in lines.iter_mut() {
+                    line.truncate(line.trim_end_matches(' ').len());
+                }
+
+                while lines.last().is_some_and(|line| line.is_empty()) {
+                    lines.pop();
+                }
+            }
+
+            lines.iter().map(|line| format!("{}\n", line)).collect()
+        }).collect();
+
+        std::fs::write(&name, planes.join("\x0c")).is_ok()
+    }"#;
+
+const BEGIN_BLOCK: &str = r#"
+    fn begin_block(&mut self, next: (i64, i64, i64)) {
+        let n = self.stack.pop().unwrap_or(0);
+        let mut toss = Vec::new();
+
+        if n > 0 {
+            let len = self.stack.len();
+            let n = n.unsigned_abs().min(len as u64 + MAX_PADDING) as usize;
+
+            if n > len {
+                toss.resize(n - len, 0);
+                toss.append(&mut self.stack);
+            } else {
+                toss.extend(self.stack.drain(len - n..));
+            }
+        } else {
+            let len = self.stack.len() + n.unsigned_abs().min(MAX_PADDING) as usize;
+            self.stack.resize(len, 0);
+        }
+
+        let offset = self.offset;
+        self.push_vector(offset);
+        self.offset = next;
+
+        let soss = std::mem::replace(&mut self.stack, toss);
+        self.stacks.push(soss);
+    }"#;
+
+const END_BLOCK: &str = r#"
+    fn end_block(&mut self) -> bool {
+        let mut soss = match self.stacks.pop() {
+            Some(soss) => soss,
+            None => return false
+        };
+
+        let n = self.stack.pop().unwrap_or(0);
+        let z = if DIMENSIONS > 2 { soss.pop().unwrap_or(0) } else { 0 };
+        let y = if DIMENSIONS > 1 { soss.pop().unwrap_or(0) } else { 0 };
+        let x = soss.pop().unwrap_or(0);
+        self.offset = (x, y, z);
+
+        if n > 0 {
+            let len = self.stack.len();
+            let n = n.unsigned_abs().min(len as u64 + MAX_PADDING) as usize;
+
+            if n > len {
+                soss.resize(soss.len() + n - len, 0);
+                soss.append(&mut self.stack);
+            } else {
+                soss.extend(self.stack.drain(len - n..));
+            }
+        } else {
+            let len = soss.len() - n.unsigned_abs().min(soss.len() as u64) as usize;
+            soss.truncate(len);
+        }
+
+        self.stack = soss;
+        true
+    }"#;
+
+const UNDER: &str = r#"
+    fn under(&mut self) -> bool {
+        let soss = match self.stacks.last_mut() {
+            Some(soss) => soss,
+            None => return false
+        };
+
+        let n = self.stack.pop().unwrap_or(0);
+
+        if n > 0 {
+            for _ in 0..n.unsigned_abs().min(soss.len() as u64 + MAX_PADDING) {
+                self.stack.push(soss.pop().unwrap_or(0));
+            }
+        } else {
+            for _ in 0..n.unsigned_abs().min(self.stack.len() as u64 + MAX_PADDING) {
+                soss.push(self.stack.pop().unwrap_or(0));
+            }
+        }
+
+        true
+    }"#;
@@ -1,58 +1,165 @@
-use std::io::File;
-use std::io::Command;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
 
-use super::Parser;
+use crate::parser::{Parser, Emit, Standard};
+use crate::interpreter::Interpreter;
+use crate::action::Action;
+use crate::error::ParserError;
+use crate::ip::IP;
+use crate::fingerprint;
 
-#[cfg(test)]
 fn compilation_test(bf: &str, file: &str, expected: &str, vars: bool, inv: bool) {
-    let bf_filename = format!("{}.b98", file);
-    let rs_filename = format!("{}.rs", file);
-
-    {
-        let mut bf_file = File::create(&Path::new(bf_filename.as_slice()));
-        match bf_file.write_line(bf) {
-            Err(e) => panic!("Error creating test befunge: {}", e),
-            _ => ()
+    compilation_test_input(bf, file, "", expected, vars, inv)
+}
+
+fn compilation_test_input(bf: &str, file: &str, input: &str, expected: &str, vars: bool, inv: bool) {
+    let rs_filename = path(file, "rs");
+
+    let p = Parser::new().enable_vars(vars).exit_on_invalid(inv);
+    write_source(&p, bf, file, &rs_filename);
+
+    let interpreted = p.read_source(bf).and_then(|code| {
+        let mut interpreter = Interpreter::new(&code, vars, inv, input.as_bytes(), Vec::new());
+        interpreter.run().map(|_| interpreter.output().clone())
+    });
+
+    match interpreted {
+        Ok(output) => assert_eq!(output, expected.as_bytes()),
+
+        Err(e) => {
+            clean_files(file);
+            panic!("Error interpreting befunge: {}", e)
         }
     }
 
-    let p = Parser::new(vars, inv, true, true, Some(rs_filename.to_string()));
-    match p.parse(&bf_filename.to_string()) {
-        Err(e) => {
+    compile(file, Command::new("rustc").args(["--edition=2021", "-O", "-o"]).arg(path(file, "")).arg(&rs_filename));
+
+    let process = Command::new(path(file, ""))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .and_then(|mut process| {
+            process.stdin.take().unwrap().write_all(input.as_bytes())?;
+            process.wait_with_output()
+        });
+
+    check_output(file, process, expected)
+}
+
+fn exit_code_test(bf: &str, file: &str, expected: i32) {
+    let rs_filename = path(file, "rs");
+
+    let p = Parser::new();
+    write_source(&p, bf, file, &rs_filename);
+
+    let mut interpreter = Interpreter::new(&p.read_source(bf).unwrap(), false, false, io::empty(), Vec::new());
+    interpreter.run().unwrap();
+    assert_eq!(interpreter.exit_code(), expected as i64);
+
+    compile(file, Command::new("rustc").args(["--edition=2021", "-O", "-o"]).arg(path(file, "")).arg(&rs_filename));
+
+    let status = Command::new(path(file, "")).status();
+    clean_files(file);
+    assert_eq!(status.unwrap().code(), Some(expected))
+}
+
+// Runs the interpreter with the same language settings as the parser
+fn configured_test(p: &Parser, bf: &str, file: &str, expected: &str) {
+    let rs_filename = path(file, "rs");
+    write_source(p, bf, file, &rs_filename);
+
+    let mut interpreter = Interpreter::new(&p.read_source(bf).unwrap(), p.vars_enabled(), p.exits_on_invalid(), io::empty(), Vec::new())
+        .dimensions(p.dimension_count())
+        .std(p.standard())
+        .enable_execute(p.execute_enabled())
+        .enable_file_io(p.file_io_enabled());
+    interpreter.run().unwrap();
+    assert_eq!(interpreter.output(), expected.as_bytes());
+
+    compile(file, Command::new("rustc").args(["--edition=2021", "-O", "-o"]).arg(path(file, "")).arg(&rs_filename));
+    check_output(file, Command::new(path(file, "")).output(), expected)
+}
+
+fn llvm_test(bf: &str, file: &str, expected: &str) {
+    let ll_filename = path(file, "ll");
+    let obj_filename = path(file, "o");
+
+    let p = Parser::new().eval(false).emit(Emit::LlvmIr);
+    write_source(&p, bf, file, &ll_filename);
+
+    // Older llc releases only accept `ptr` with opaque pointers switched on explicitly
+    let llc = |extra: &[&str]| Command::new("llc").args(extra).args(["-filetype=obj", "-relocation-model=pic", "-o"]).arg(&obj_filename).arg(&ll_filename).output();
+    match llc(&[]) {
+        Ok(ref output) if output.status.success() => (),
+        _ => compile(file, Command::new("llc").args(["-opaque-pointers", "-filetype=obj", "-relocation-model=pic", "-o"]).arg(&obj_filename).arg(&ll_filename))
+    }
+
+    compile(file, Command::new("cc").arg("-o").arg(path(file, "")).arg(&obj_filename));
+
+    check_output(file, Command::new(path(file, "")).output(), expected)
+}
+
+fn c_test(bf: &str, file: &str, expected: &str) {
+    let c_filename = path(file, "c");
+
+    let p = Parser::new().eval(false).emit(Emit::C);
+    write_source(&p, bf, file, &c_filename);
+
+    compile(file, Command::new("cc").arg("-o").arg(path(file, "")).arg(&c_filename));
+
+    check_output(file, Command::new(path(file, "")).output(), expected)
+}
+
+fn path(file: &str, extension: &str) -> PathBuf {
+    env::temp_dir().join(format!("befunge_test_{}", file)).with_extension(extension)
+}
+
+fn compile(file: &str, command: &mut Command) {
+    match command.output() {
+        Ok(ref output) if output.status.success() => (),
+
+        Ok(output) => {
             clean_files(file);
-            panic!("Error parsing befunge: {}", e)
+            panic!("Compilation failed: {}", String::from_utf8_lossy(&output.stderr))
         },
-        _ => ()
-    }
 
-    match Command::new("rustc").arg(rs_filename.as_slice()).output() {
         Err(e) => {
             clean_files(file);
             panic!("Compilation process error: {}", e)
-        },
-
-        _ => ()
+        }
     }
+}
 
-    match Command::new(format!("./{}", file)).output() {
-        Ok(output) => assert_eq!(output.output.as_slice(), expected.as_bytes()),
+fn write_source(p: &Parser, bf: &str, file: &str, filename: &PathBuf) {
+    let code = match p.compile(bf) {
+        Ok(code) => code,
 
         Err(e) => {
             clean_files(file);
-            panic!("Error running compiled program: {}", e)
+            panic!("Error parsing befunge: {}", e)
         }
+    };
+
+    if let Err(e) = fs::write(filename, code) {
+        panic!("Error writing generated code: {}", e)
     }
+}
 
-    clean_files(file)
+fn check_output(file: &str, output: io::Result<Output>, expected: &str) {
+    clean_files(file);
+
+    match output {
+        Ok(output) => assert_eq!(String::from_utf8_lossy(&output.stdout), expected),
+        Err(e) => panic!("Error running compiled program: {}", e)
+    }
 }
 
-#[cfg(test)]
 fn clean_files(file: &str) {
-    let bf_filename = format!("{}.b98", file);
-    let rs_filename = format!("{}.rs", file);
-
-    for f in vec![bf_filename.as_slice(), rs_filename.as_slice(), file].iter() {
-        Command::new("rm").arg(*f).spawn();
+    for extension in ["rs", "ll", "o", "c", ""] {
+        let _ = fs::remove_file(path(file, extension));
     }
 }
 
@@ -62,7 +169,7 @@ fn test_simple() {
 }
 
 #[test]
-#[should_fail]
+#[should_panic]
 fn test_invalid_char() {
     compilation_test("a.m@", "inv_char", "", false, false);
 }
@@ -70,10 +177,11 @@ fn test_invalid_char() {
 #[test]
 fn test_ignore_invalid_char() {
     compilation_test("a.m@", "inv_char_2", "10", false, true);
+    compilation_test("\"h\"60p5 1.@", "inv_char_put", "1", true, true);
 }
 
 #[test]
-#[should_fail]
+#[should_panic]
 fn test_disable_var() {
     compilation_test("555p55g.@", "var_dis", "", false, false);
 }
@@ -83,7 +191,481 @@ fn test_enable_var() {
     compilation_test("555p55g.@", "var_en", "5", true, false);
 }
 
+#[test]
+fn test_get_source() {
+    compilation_test("20g,@", "get_src", "g", true, false);
+}
+
+#[test]
+fn test_self_modify() {
+    compilation_test("5\".\"70p @", "self_mod", "5", true, false);
+}
+
+#[test]
+fn test_store() {
+    compilation_test("5\".\"s v\n     ^<\n     @", "store", "5", true, false);
+}
+
 #[test]
 fn test_jump() {
     compilation_test("2j234.@", "jump", "4", false, false);
 }
+
+#[test]
+fn test_jump_large() {
+    // Huge counts only walk what is left over after whole laps of the row
+    compilation_test("ff*:*:*:*j@5.@", "jump_large", "5", false, false);
+    compilation_test("0ff*:*:*:*-j@@@@@@5.@", "jump_large_back", "5", false, false);
+    compilation_test("\"z\"00pff*:*:*:*j@@@5.@@@@@", "jump_large_interpret", "5", true, false);
+    configured_test(&Parser::new().j_eval(false), "ff*:*:*:*j@5.@", "jump_large_runtime", "5");
+}
+
+#[test]
+fn test_comment() {
+    compilation_test("1.;xyz;2.@", "comment", "12", false, false);
+}
+
+#[test]
+fn test_comment_wrap() {
+    compilation_test("    v\nm;.@>1;", "comment_wrap", "1", false, false);
+}
+
+#[test]
+fn test_iterate() {
+    compilation_test("1233k.@", "iterate", "321", false, false);
+}
+
+#[test]
+fn test_iterate_nop() {
+    compilation_test("1z2z+.@", "nop", "3", false, false);
+    compilation_test("5 2kz.@", "iterate_nop", "5", false, false);
+}
+
+#[test]
+fn test_iterate_zero() {
+    compilation_test("10k ;x; 2.@", "iterate_zero", "1", false, false);
+}
+
+#[test]
+fn test_iterate_direction() {
+    compilation_test("7 2k r.@", "iterate_dir", "7", false, false);
+}
+
+#[test]
+fn test_iterate_trampoline() {
+    compilation_test("2k#123.@", "iterate_tramp", "3", false, false);
+}
+
+#[test]
+fn test_iterate_runtime() {
+    compilation_test_input("1234&k.@", "iterate_rt", "3", "432", false, false);
+}
+
+#[test]
+fn test_iterate_fallback() {
+    compilation_test_input("5&kr.@", "iterate_fallback", "2", "5", false, false);
+}
+
+#[test]
+fn test_iterate_countdown() {
+    compilation_test_input("&k#123.@", "iterate_countdown", "2", "3", false, false);
+    compilation_test_input("1&k_2.@", "iterate_countdown_branch", "2", "2", false, false);
+    configured_test(&Parser::new().j_eval(false), "2k#123.@", "iterate_countdown_const", "3");
+
+    // Only instructions that write to the grid need the embedded interpreter
+    let graph = Parser::new().parse("&kw@").unwrap();
+    assert!(graph.states.iter().flatten().all(|action| !matches!(action, Action::Interpret(..))));
+}
+
+#[test]
+fn test_iterate_large() {
+    compilation_test("5ff*k:1+.@", "iterate_large", "6", false, false);
+    compilation_test("1 0ff*k_2.@", "iterate_large_branch", "2", false, false);
+
+    // Large constant counts loop at runtime instead of being unrolled
+    let graph = Parser::new().parse("ff*ff*ff***k$@").unwrap();
+    assert!(graph.states.iter().map(Vec::len).sum::<usize>() < 16);
+}
+
+#[test]
+fn test_vector() {
+    compilation_test("11x   \n   5  \n    . \n     @", "vector", "5", false, false);
+}
+
+#[test]
+fn test_vector_wrap() {
+    compilation_test("5#@30x  . ", "vector_wrap", "5", false, false);
+}
+
+#[test]
+fn test_vector_runtime() {
+    compilation_test_input("&&x 5 . @", "vector_rt", "1 0", "5", false, false);
+}
+
+#[test]
+fn test_vector_runtime_fallback() {
+    compilation_test_input("&&x 5 . @", "vector_rt_fallback", "2 0", "5", false, false);
+}
+
+#[test]
+fn test_block() {
+    compilation_test("1232{..0}..@", "block", "3210", false, false);
+}
+
+#[test]
+fn test_block_large() {
+    compilation_test("7ff*:*:*:*{.@", "block_large", "7", false, false);
+    compilation_test("10ff*:*:*:*-{}..@", "block_large_pad", "00", false, false);
+    compilation_test("1230{0ff*:*:*:*-}..@", "block_large_pop", "00", false, false);
+    compilation_test("0{ff*:*:*:*u.@", "under_large", "0", false, false);
+}
+
+#[test]
+fn test_under() {
+    compilation_test("50{3u.@", "under", "5", false, false);
+}
+
+#[test]
+fn test_block_reflect() {
+    compilation_test("5#@.}", "block_reflect", "50", false, false);
+    compilation_test("5#@.u", "under_reflect", "50", false, false);
+}
+
+#[test]
+fn test_storage_offset() {
+    compilation_test("0{10g,@", "storage_offset", "0", true, false);
+}
+
+#[test]
+fn test_fingerprint() {
+    compilation_test(r#""AMOR"4($$XI+.@"#, "fingerprint", "11", false, false);
+    compilation_test(r#""AMOR"4($$"LOOB"4($$63X."LOOB"4)X.@"#, "fingerprint_overload", "510", false, false);
+}
+
+#[test]
+fn test_fingerprint_modu() {
+    compilation_test(r#""UDOM"4($$07-3M.07-3U.07-3R.@"#, "fingerprint_modu", "21-1", false, false);
+}
+
+#[test]
+fn test_fingerprint_hrti() {
+    compilation_test(r#""ITRH"4($$G.MT1+0`.@"#, "fingerprint_hrti", "11", false, false);
+    compilation_test(r#""ITRH"4($$5#@.T"#, "fingerprint_hrti_unmarked", "50", false, false);
+    // Erasing one IP's mark leaves the others' alone
+    compilation_test(r#""ITRH"4($$M2j@Et#@T$1.@"#, "fingerprint_hrti_split", "1", false, false);
+}
+
+#[test]
+fn test_fingerprint_disabled() {
+    let code = Parser::new().enable_fingerprint("ROMA", false).compile(r#""AMOR"4(@"#).unwrap();
+    assert!(code.contains("const FINGERPRINTS: &[i64] = &[0x4e554c4c, 0x4d4f4455, 0x424f4f4c, 0x48525449];"));
+}
+
+#[test]
+fn test_fingerprint_reflect() {
+    compilation_test(r#""ABCD"4 5#@.("#, "fingerprint_unknown", "50", false, false);
+    compilation_test(r#""AMOR"4($$"LLUN"4($$5#@.I"#, "fingerprint_null", "50", false, false);
+    compilation_test(r#""AMOR"4($$"AMOR"4)5#@.I"#, "fingerprint_unload", "50", false, false);
+}
+
+#[test]
+fn test_input_number() {
+    compilation_test_input("&&+.@", "input_num", "abc12 -5\n", "7", false, false);
+}
+
+#[test]
+fn test_input_number_eof() {
+    compilation_test_input("&.@", "input_num_eof", "", "-1", false, false);
+}
+
+#[test]
+fn test_modulo_not_greater() {
+    compilation_test("73%.0!.25`.52`.@", "mod_not_gt", "1101", false, false);
+}
+
+#[test]
+fn test_modulo_not_greater_runtime() {
+    compilation_test_input("&3%.&!.&&`.@", "mod_not_gt_rt", "7 0 2 5", "110", false, false);
+}
+
+#[test]
+fn test_llvm_simple() {
+    llvm_test("0\"olleH\">:#,_@", "llvm_simp", "Hello");
+}
+
+#[test]
+fn test_llvm_arithmetic() {
+    llvm_test("73%.0!.25`.52`.92-.@", "llvm_arith", "11017");
+}
+
+#[test]
+fn test_llvm_iterate() {
+    llvm_test("1233k.@", "llvm_iterate", "321");
+    llvm_test("12+k#1234.@", "llvm_countdown", "4");
+}
+
+#[test]
+fn test_llvm_quit() {
+    llvm_test("3.7q", "llvm_quit", "3");
+}
+
+#[test]
+fn test_c_simple() {
+    c_test("0\"olleH\">:#,_@", "c_simp", "Hello");
+}
+
+#[test]
+fn test_c_arithmetic() {
+    c_test("73%.0!.25`.52`.92-.@", "c_arith", "11017");
+}
+
+#[test]
+fn test_c_iterate() {
+    c_test("1233k.@", "c_iterate", "321");
+    c_test("12+k#1234.@", "c_countdown", "4");
+}
+
+#[test]
+fn test_long_loop() {
+    compilation_test(">1+:\"d\"::**`!v\n^            _.@", "long_loop", "1000001", false, false);
+}
+
+#[test]
+fn test_compile_source() {
+    let code = Parser::new().compile("1.@").unwrap();
+    assert!(code.contains("fn state0(&mut self)"));
+}
+
+#[test]
+fn test_parse_state_graph() {
+    let graph = Parser::new().parse("#@1_@").unwrap();
+    assert_eq!(graph.states[0], vec![Action::PushNumber(1), Action::If(1, 2)]);
+}
+
+#[test]
+fn test_split() {
+    compilation_test("2j@.2t1.@", "split", "21", false, false);
+    compilation_test("5 1j.t..@", "split_stack", "550", false, false);
+    compilation_test("12t3k.@", "split_iterate", "210", false, false);
+    compilation_test("\"zyx\"t9k.@@p00\"A\"", "split_interpret", "120121122000000", true, false);
+    compilation_test("3j@.2      t1z.@", "split_spaces", "21", false, false);
+    compilation_test("\"z\"00p3j@.2  ;;  t1z.@", "split_spaces_interpret", "21", true, false);
+
+    // Each IP gets its own turn through the compiled states
+    let graph = Parser::new().parse("2j@.2t1.@").unwrap();
+    assert!(graph.states.iter().flatten().all(|action| !matches!(action, Action::Interpret(..))));
+
+    // A 't' that is never run doesn't split the program into single ticks
+    assert_eq!(Parser::new().parse("\"tseT\",,,,@").unwrap().states.len(), 1);
+}
+
+#[test]
+fn test_quit() {
+    compilation_test("3.0q.@", "quit", "3", false, false);
+    exit_code_test("7q", "quit_code", 7);
+    exit_code_test("7t q", "quit_split", 7);
+}
+
+#[test]
+fn test_execute_disabled() {
+    compilation_test("0\"eurt\"=.1y8/2%.@", "execute_disabled", "-10", false, false);
+}
+
+#[test]
+fn test_execute() {
+    let bf = "0\"3 tixe\"=.1y8/2%.@";
+    let rs_filename = path("execute", "rs");
+
+    let p = Parser::new().enable_execute(true);
+    write_source(&p, bf, "execute", &rs_filename);
+
+    let mut interpreter = Interpreter::new(&p.read_source(bf).unwrap(), false, false, io::empty(), Vec::new()).enable_execute(true);
+    interpreter.run().unwrap();
+    assert_eq!(interpreter.output(), b"31");
+
+    compile("execute", Command::new("rustc").args(["--edition=2021", "-O", "-o"]).arg(path("execute", "")).arg(&rs_filename));
+    check_output("execute", Command::new(path("execute", "")).output(), "31")
+}
+
+#[test]
+fn test_file_io_disabled() {
+    compilation_test("5#@.i", "file_input_disabled", "50", false, false);
+    compilation_test("5#@.o", "file_output_disabled", "50", false, false);
+}
+
+#[test]
+fn test_file_io() {
+    let file = path("file_io", "txt");
+    let name: String = file.to_string_lossy().chars().rev().collect();
+    let bf = format!("3121001 0\"{}\"o01 0 0\"{}\"i$$$$01g,11g,@", name, name);
+    let rs_filename = path("file_io", "rs");
+
+    let p = Parser::new().enable_vars(true).enable_file_io(true);
+    write_source(&p, &bf, "file_io", &rs_filename);
+
+    let mut interpreter = Interpreter::new(&p.read_source(&bf).unwrap(), true, false, io::empty(), Vec::new()).enable_file_io(true);
+    interpreter.run().unwrap();
+    assert_eq!(interpreter.output(), b"31");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "31\n");
+
+    compile("file_io", Command::new("rustc").args(["--edition=2021", "-O", "-o"]).arg(path("file_io", "")).arg(&rs_filename));
+    let _ = fs::remove_file(&file);
+
+    let output = Command::new(path("file_io", "")).output();
+    let written = fs::read_to_string(&file);
+    let _ = fs::remove_file(&file);
+
+    check_output("file_io", output, "31");
+    assert_eq!(written.unwrap(), "31\n")
+}
+
+#[test]
+fn test_file_input_overwrite() {
+    let file = path("file_input_overwrite", "txt");
+    let name: String = file.to_string_lossy().chars().rev().collect();
+    fs::write(&file, "7").unwrap();
+
+    // Loading over a compiled cell hands the rest of the program to the embedded interpreter
+    let top = format!("21 0 0\"{}\"i$$$$v", name);
+    let bf = format!("{}\n@.1{}<", top, " ".repeat(top.len() - 4));
+    configured_test(&Parser::new().enable_file_io(true), &bf, "file_input_overwrite", "7");
+    let _ = fs::remove_file(&file);
+
+    // Otherwise 'i' just branches between compiled states
+    let graph = Parser::new().parse("5#@.i").unwrap();
+    assert!(graph.states.iter().flatten().all(|action| !matches!(action, Action::Interpret(..))));
+}
+
+#[test]
+fn test_system_info() {
+    compilation_test("2y.7y.8y.9y.@", "system_info", "8200", false, false);
+    compilation_test("ay.by.cy.dy.f4+y.@", "system_info_ip", "040117", false, false);
+
+    // The operating paradigm is system() only when '=' is allowed to run commands
+    compilation_test("5y.@", "system_info_paradigm", "0", false, false);
+    configured_test(&Parser::new().enable_execute(true), "5y.@", "system_info_paradigm_execute", "1");
+}
+
+#[test]
+fn test_handprint() {
+    let code = Parser::new().handprint(fingerprint::id("TEST")).compile("3y.@").unwrap();
+    assert!(code.contains("const HANDPRINT: i64 = 0x54455354;"));
+}
+
+#[test]
+fn test_random() {
+    compilation_test("?1.@.1", "random", "1", false, false);
+}
+
+#[test]
+fn test_lahey_diagonal() {
+    let mut ip = IP::new(3, 1, 1, 1);
+
+    ip.advance(5, 3, 1);
+    assert_eq!((ip.x, ip.y), (4, 2));
+
+    ip.advance(5, 3, 1);
+    assert_eq!((ip.x, ip.y), (2, 0));
+}
+
+#[test]
+fn test_lahey_large_delta() {
+    let mut ip = IP::new(4, 0, 3, 0);
+    ip.advance(5, 3, 1);
+    assert_eq!((ip.x, ip.y), (1, 0));
+
+    let mut ip = IP::new(1, 1, -7, 0);
+    ip.advance(5, 3, 1);
+    assert_eq!((ip.x, ip.y), (1, 1));
+
+    let mut ip = IP::new(0, 0, 2, 5);
+    ip.advance(5, 3, 1);
+    assert_eq!((ip.x, ip.y), (0, 0));
+}
+
+#[test]
+fn test_lahey_non_rectangular() {
+    let code = Parser::new().read_source("v\n\n>  @   \n  \n").unwrap();
+    assert_eq!((code[0][0].len(), code[0].len()), (4, 3));
+
+    let mut ip = IP::new(0, 0, 0, -1);
+    ip.advance(4, 3, 1);
+    assert_eq!((ip.x, ip.y), (0, 2));
+
+    let mut ip = IP::new(1, 1, 1, 0);
+    ip.advance(4, 3, 1);
+    ip.advance(4, 3, 1);
+    ip.advance(4, 3, 1);
+    assert_eq!((ip.x, ip.y), (0, 1));
+}
+
+#[test]
+fn test_trefunge_planes() {
+    let code = Parser::new().dimensions(3).read_source("12\n3\x0c\x0c4\x0c\n").unwrap();
+    assert_eq!((code[0][0].len(), code[0].len(), code.len()), (2, 2, 3));
+    assert_eq!(code[2][0], vec!['4', ' ']);
+}
+
+#[test]
+fn test_trefunge() {
+    let p = Parser::new().dimensions(3);
+    configured_test(&p, "1.h\x0c  >2.@", "trefunge_high", "12");
+    configured_test(&p, "1m\x0c >4.@", "trefunge_if_high", "4");
+    configured_test(&p, "0m\x0c >4.@\x0c >5.@", "trefunge_if_low", "5");
+    configured_test(&p, "7y.l\x0c\x0c   >6.@", "trefunge_low", "36");
+    configured_test(&p, ">?1.@\x0c 1", "trefunge_random", "1");
+
+    // '?' picks between compiled states for all six directions
+    let graph = p.parse(">?1.@\x0c 1").unwrap();
+    assert!(graph.states.iter().flatten().any(|action| matches!(action, Action::Random(targets) if targets.len() == 6)));
+}
+
+#[test]
+fn test_trefunge_vectors() {
+    configured_test(&Parser::new().dimensions(3), "001x\x0c   >6.@", "trefunge_delta", "6");
+    configured_test(&Parser::new().dimensions(3).enable_vars(true), "101g,@\x0c A", "trefunge_get", "A");
+}
+
+#[test]
+fn test_unefunge() {
+    let p = Parser::new().dimensions(1);
+    configured_test(&p, "1.\n2.@", "unefunge", "12");
+    configured_test(&p, "<@.\n3", "unefunge_wrap", "3");
+    configured_test(&p, "7y.2x.5@.0@", "unefunge_vectors", "15");
+}
+
+#[test]
+fn test_unefunge_rejects_2d() {
+    let p = Parser::new().dimensions(1);
+    assert!(matches!(p.parse("1v@"), Err(ParserError::UnsupportedInstruction('v', _))));
+    assert!(p.parse("\"^v|[]w\"@").is_ok());
+    assert!(p.clone().exit_on_invalid(true).parse("1v@").is_ok());
+}
+
+#[test]
+fn test_befunge93_grid() {
+    let code = Parser::new().std(Standard::Befunge93).read_source("1.@\n").unwrap();
+    assert_eq!((code[0][0].len(), code[0].len()), (80, 25));
+
+    let wide = "1".repeat(81);
+    assert!(matches!(Parser::new().std(Standard::Befunge93).read_source(&wide), Err(ParserError::SourceTooLarge(81, 1))));
+}
+
+#[test]
+fn test_befunge93_instructions() {
+    let p = Parser::new().std(Standard::Befunge93);
+    assert!(matches!(p.parse("3k.@"), Err(ParserError::UnsupportedInstruction('k', _))));
+    assert!(matches!(p.parse("a.@"), Err(ParserError::UnsupportedInstruction('a', _))));
+    assert!(p.parse("\"kaw\",,,@").is_ok());
+
+    // They're still just invalid characters, which -e ignores
+    configured_test(&p.clone().exit_on_invalid(true), "a3k.@", "befunge93_invalid", "3");
+    configured_test(&p.clone().exit_on_invalid(true).enable_vars(true), "\"k\"60p2 1.@", "befunge93_invalid_put", "1");
+}
+
+#[test]
+fn test_befunge93_torus() {
+    let p = Parser::new().std(Standard::Befunge93).enable_vars(true);
+    configured_test(&p, "\"A\"\"P\"0p\"P\"0g.01-0g.@", "befunge93_torus", "00");
+    configured_test(&p, "\"A\"\"O\"0p\"O\"0g.@", "befunge93_edge", "65");
+}